@@ -0,0 +1,37 @@
+"use strict";
+
+// The core compiled to WebAssembly by `npm run build`
+const wasm = require("./wasm/nhtml.js");
+
+// An error in a page, with the position bundlers show in their overlays
+class NhtmlError extends Error {
+  constructor(diagnostic) {
+    super(diagnostic.message);
+    this.name = "NhtmlError";
+    this.code = diagnostic.code;
+    this.diagnostic = diagnostic;
+    // The snippet the command line would print
+    this.frame = diagnostic.rendered;
+
+    if (diagnostic.start_line !== undefined) {
+      this.loc = {
+        file: diagnostic.file ?? undefined,
+        line: diagnostic.start_line,
+        column: diagnostic.start_column,
+      };
+    }
+  }
+}
+
+// Transpiles a page to HTML, throwing an `NhtmlError` for the first error in
+// it. Errors point at `filename` if it is given.
+function transpile(source, { filename } = {}) {
+  const { html, diagnostics } = wasm.transpile(source, filename);
+  if (html === null) {
+    throw new NhtmlError(diagnostics[0]);
+  }
+
+  return html;
+}
+
+module.exports = { transpile, NhtmlError };
@@ -0,0 +1,73 @@
+// Needs the WebAssembly build, made with `npm run build`
+"use strict";
+
+const assert = require("node:assert");
+const fs = require("node:fs");
+const os = require("node:os");
+const path = require("node:path");
+const { test } = require("node:test");
+
+const { transpile, NhtmlError } = require("../index.js");
+const nhtml = require("../vite.js");
+const loader = require("../webpack-loader.js");
+
+test("transpile", () => {
+  assert.strictEqual(transpile("p 'Hi';"), "<p>\n    Hi\n</p>\n");
+
+  assert.throws(() => transpile("div {\n  p $;\n}", { filename: "index.nhtml" }), (err) => {
+    assert.ok(err instanceof NhtmlError);
+    assert.strictEqual(err.code, "E0001");
+    assert.deepStrictEqual(err.loc, { file: "index.nhtml", line: 2, column: 5 });
+    assert.match(err.frame, /--> index\.nhtml:2:5/);
+    return true;
+  });
+});
+
+test("vite plugin", () => {
+  const root = fs.mkdtempSync(path.join(os.tmpdir(), "nhtml-vite-"));
+  fs.writeFileSync(path.join(root, "about.nhtml"), "p 'About';");
+
+  const plugin = nhtml();
+  plugin.configResolved({ root });
+
+  const module = plugin.transform("p 'Hi';", path.join(root, "hi.nhtml"));
+  assert.strictEqual(module.code, 'export default "<p>\\n    Hi\\n</p>\\n";');
+  assert.strictEqual(plugin.transform("p {}", path.join(root, "style.css")), null);
+
+  // Entry points are built as the `.html` file next to them
+  const id = plugin.resolveId("about.nhtml", undefined);
+  assert.strictEqual(id, path.join(root, "about.html"));
+  assert.strictEqual(plugin.resolveId("about.nhtml", path.join(root, "main.js")), null);
+
+  const watched = [];
+  const html = plugin.load.call({ addWatchFile: (file) => watched.push(file) }, id);
+  assert.strictEqual(html, "<p>\n    About\n</p>\n");
+  assert.deepStrictEqual(watched, [path.join(root, "about.nhtml")]);
+  assert.strictEqual(plugin.load.call({}, path.join(root, "index.html")), null);
+
+  assert.throws(() => plugin.transform("p $;", path.join(root, "bad.nhtml")), (err) => {
+    assert.deepStrictEqual(err.loc, { file: path.join(root, "bad.nhtml"), line: 1, column: 3 });
+    return true;
+  });
+
+  fs.rmSync(root, { recursive: true });
+});
+
+test("webpack loader", () => {
+  const run = (source, options = {}) => {
+    let error;
+    const context = {
+      resourcePath: "/src/index.nhtml",
+      getOptions: () => options,
+      callback: (err) => { error = err; },
+    };
+    return { code: loader.call(context, source), error };
+  };
+
+  assert.strictEqual(run("p 'Hi';").code, 'export default "<p>\\n    Hi\\n</p>\\n";');
+  assert.strictEqual(run("p 'Hi';", { esModule: false }).code, 'module.exports = "<p>\\n    Hi\\n</p>\\n";');
+
+  const { code, error } = run("p $;");
+  assert.strictEqual(code, undefined);
+  assert.match(error.message, /^error\[E0001\]: Invalid character '\$'\n --> \/src\/index\.nhtml:1:3/);
+});
@@ -0,0 +1,89 @@
+"use strict";
+
+const fs = require("node:fs");
+const path = require("node:path");
+
+const { transpile } = require("./index.js");
+
+// Vite plugin for `.nhtml` files. Importing a page gives its HTML as a
+// string, and pages can be entry points like `.html` files: `about.nhtml` is
+// served and built as `about.html` unless there is an `about.html` already.
+function nhtml() {
+  let root = process.cwd();
+
+  return {
+    name: "nhtml",
+    enforce: "pre",
+
+    configResolved(config) {
+      root = config.root;
+    },
+
+    // Entry points are resolved to the `.html` file they are built as
+    resolveId(source, importer) {
+      if (importer !== undefined || !source.endsWith(".nhtml")) {
+        return null;
+      }
+
+      return htmlPath(path.resolve(root, source));
+    },
+
+    load(id) {
+      const page = pageFor(id);
+      if (page === null) {
+        return null;
+      }
+
+      this.addWatchFile(page);
+      return transpileFile(page);
+    },
+
+    transform(code, id) {
+      if (!id.endsWith(".nhtml")) {
+        return null;
+      }
+
+      return { code: `export default ${JSON.stringify(transpileFile(id, code))};`, map: null };
+    },
+
+    configureServer(server) {
+      server.middlewares.use(async (req, res, next) => {
+        const url = decodeURIComponent(req.url.split("?")[0]);
+        const file = path.join(root, url.endsWith("/") ? `${url}index.html` : url);
+        const page = file.startsWith(root) ? pageFor(file) : null;
+        if (page === null) {
+          return next();
+        }
+
+        try {
+          const html = await server.transformIndexHtml(req.url, transpileFile(page));
+          res.setHeader("Content-Type", "text/html");
+          res.end(html);
+        } catch (err) {
+          next(err);
+        }
+      });
+    },
+  };
+}
+
+function htmlPath(page) {
+  return page.slice(0, -".nhtml".length) + ".html";
+}
+
+// The page an `.html` file is built from, if there is no such file
+function pageFor(id) {
+  if (!id.endsWith(".html") || fs.existsSync(id)) {
+    return null;
+  }
+
+  const page = id.slice(0, -".html".length) + ".nhtml";
+  return fs.existsSync(page) ? page : null;
+}
+
+function transpileFile(file, source = fs.readFileSync(file, "utf8")) {
+  return transpile(source, { filename: file });
+}
+
+module.exports = nhtml;
+module.exports.default = nhtml;
@@ -0,0 +1,28 @@
+"use strict";
+
+const { transpile, NhtmlError } = require("./index.js");
+
+// webpack loader for `.nhtml` files, giving a module that exports the HTML.
+// Pages can be entry points as templates of html-webpack-plugin:
+// `new HtmlWebpackPlugin({ template: "src/index.nhtml" })`.
+module.exports = function nhtmlLoader(source) {
+  const { esModule = true } = this.getOptions();
+
+  let html;
+  try {
+    html = transpile(source, { filename: this.resourcePath });
+  } catch (err) {
+    // webpack only shows the message, so it gets the snippet with the
+    // position in the page
+    if (err instanceof NhtmlError && err.frame) {
+      err.message = err.frame;
+      err.hideStack = true;
+    }
+
+    this.callback(err);
+    return;
+  }
+
+  const json = JSON.stringify(html);
+  return esModule ? `export default ${json};` : `module.exports = ${json};`;
+};
@@ -0,0 +1,279 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use pyo3::{
+    create_exception,
+    exceptions::{PyTypeError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyDict, PyList, PyString},
+};
+
+use ::nhtml::{
+    config::Options,
+    diagnostic::Diagnostic,
+    parser::{Attrib, Element, Value},
+    position,
+    render::{self, Style},
+    variables::Variable,
+};
+
+create_exception!(nhtml, NhtmlError, PyValueError, "An error in a page. Has the `code` of the error, the `filename` and the `line` and `column` it starts at.");
+
+// Where a node is in the page. Lines and columns start at 1, and the end
+// column is inclusive.
+#[pyclass(frozen, get_all, module = "nhtml")]
+#[derive(Clone)]
+struct Position {
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+    // Byte offset and length in the source
+    offset: usize,
+    length: usize,
+}
+
+#[pymethods]
+impl Position {
+    fn __repr__(&self) -> String {
+        format!("Position({}:{} to {}:{})", self.line, self.column, self.end_line, self.end_column)
+    }
+}
+
+impl From<position::Position> for Position {
+    fn from(pos: position::Position) -> Self {
+        Self {
+            line: pos.start_ln,
+            column: pos.start_cn,
+            end_line: pos.end_ln,
+            end_column: pos.end_cn,
+            offset: pos.idx,
+            length: pos.len,
+        }
+    }
+}
+
+// A tag with its attributes, in the order they were written, and children.
+// `pos` goes from the name to the end of the body.
+#[pyclass(frozen, get_all, module = "nhtml")]
+struct Tag {
+    name: String,
+    attributes: Py<PyList>,
+    children: Py<PyList>,
+    pos: Position,
+    name_pos: Position,
+}
+
+#[pymethods]
+impl Tag {
+    fn __repr__(&self) -> String {
+        format!("Tag({:?})", self.name)
+    }
+}
+
+// An attribute. Values are strings, `True` for attributes without one and
+// `False` for ones that are left out. `class` and `style` maps are dicts.
+#[pyclass(frozen, get_all, module = "nhtml")]
+struct Attribute {
+    name: String,
+    value: PyObject,
+    pos: Position,
+}
+
+// `...name` with the attributes of the variable
+#[pyclass(frozen, get_all, module = "nhtml")]
+struct Spread {
+    name: String,
+    attributes: Py<PyDict>,
+    pos: Position,
+}
+
+#[pyclass(frozen, get_all, module = "nhtml")]
+struct Text {
+    text: String,
+    pos: Position,
+}
+
+// A raw `html`, `js` or `css` block
+#[pyclass(frozen, get_all, module = "nhtml")]
+struct Block {
+    kind: &'static str,
+    content: String,
+    pos: Position,
+}
+
+// Options are keyword arguments, like
+// `transpile(source, filename="index.nhtml", minify=True)`
+fn options(indent: usize, minify: bool, variables: Option<&Bound<PyDict>>) -> PyResult<Options> {
+    let mut opts = Options::default();
+    opts.emit.indent = indent;
+    opts.emit.minify = minify;
+
+    if let Some(variables) = variables {
+        opts.variables = to_variables(variables)?;
+    }
+
+    Ok(opts)
+}
+
+fn to_variables(dict: &Bound<PyDict>) -> PyResult<BTreeMap<String, Variable>> {
+    dict.iter()
+        .map(|(name, value)| Ok((name.extract()?, to_variable(&value)?)))
+        .collect()
+}
+
+fn to_variable(value: &Bound<PyAny>) -> PyResult<Variable> {
+    // `bool` is a subclass of `int`, so it comes first
+    if let Ok(b) = value.downcast::<PyBool>() {
+        return Ok(Variable::Bool(b.is_true()));
+    }
+
+    if let Ok(dict) = value.downcast::<PyDict>() {
+        return Ok(Variable::Attributes(to_variables(dict)?));
+    }
+
+    if let Ok(text) = value.downcast::<PyString>() {
+        return Ok(Variable::Text(text.to_str()?.to_owned()));
+    }
+
+    match value.extract::<i64>() {
+        Ok(n) => Ok(Variable::Number(n)),
+        Err(_) => Err(PyTypeError::new_err(format!("Variables can be str, int, bool or dict, not {}", value.get_type().name()?))),
+    }
+}
+
+fn to_py_err(py: Python<'_>, err: &anyhow::Error) -> PyErr {
+    let diag = Diagnostic::from_error(err);
+    let py_err = NhtmlError::new_err(render::render(&diag, Style { color: false }).trim_end().to_owned());
+
+    let pos = diag.pos();
+    let value = py_err.value(py);
+    let set = || -> PyResult<()> {
+        value.setattr("code", diag.code)?;
+        value.setattr("message", &diag.message)?;
+        value.setattr("filename", diag.path.as_ref().map(|path| path.display().to_string()))?;
+        value.setattr("line", pos.map(|pos| pos.start_ln))?;
+        value.setattr("column", pos.map(|pos| pos.start_cn))?;
+        value.setattr("end_line", pos.map(|pos| pos.end_ln))?;
+        value.setattr("end_column", pos.map(|pos| pos.end_cn))
+    };
+
+    match set() {
+        Ok(()) => py_err,
+        Err(err) => err,
+    }
+}
+
+fn to_py_elements(py: Python<'_>, elements: &[Element]) -> PyResult<Py<PyList>> {
+    let list = PyList::empty(py);
+    for element in elements {
+        let obj = match element {
+            Element::Tag(tag) => {
+                let attributes = PyList::empty(py);
+                for attrib in &tag.attribs {
+                    attributes.append(to_py_attrib(py, attrib)?)?;
+                }
+
+                Tag {
+                    name: tag.ty.node.clone(),
+                    attributes: attributes.unbind(),
+                    children: to_py_elements(py, &tag.body)?,
+                    pos: tag.pos.into(),
+                    name_pos: tag.ty.pos.into(),
+                }.into_pyobject(py)?.into_any()
+            },
+            Element::Text(text) => Text { text: text.node.clone(), pos: text.pos.into() }.into_pyobject(py)?.into_any(),
+            Element::Html(block) => to_py_block(py, "html", block)?,
+            Element::Js(block) => to_py_block(py, "js", block)?,
+            Element::Css(block) => to_py_block(py, "css", block)?,
+        };
+
+        list.append(obj)?;
+    }
+
+    Ok(list.unbind())
+}
+
+fn to_py_block<'py>(py: Python<'py>, kind: &'static str, block: &position::Spanned<String>) -> PyResult<Bound<'py, PyAny>> {
+    Ok(Block { kind, content: block.node.clone(), pos: block.pos.into() }.into_pyobject(py)?.into_any())
+}
+
+fn to_py_attrib<'py>(py: Python<'py>, attrib: &Attrib) -> PyResult<Bound<'py, PyAny>> {
+    match attrib {
+        Attrib::Named { name, value } => {
+            let value = match value {
+                Some(value) => to_py_value(py, value)?,
+                None => true.into_pyobject(py)?.to_owned().into_any().unbind(),
+            };
+
+            Ok(Attribute { name: name.node.clone(), value, pos: attrib.pos().into() }.into_pyobject(py)?.into_any())
+        },
+        Attrib::Spread { name, attribs } => {
+            let attributes = PyDict::new(py);
+            for (attr, value) in attribs {
+                let value = match value {
+                    Some(value) => to_py_value(py, value)?,
+                    None => true.into_pyobject(py)?.to_owned().into_any().unbind(),
+                };
+                attributes.set_item(attr, value)?;
+            }
+
+            Ok(Spread { name: name.node.clone(), attributes: attributes.unbind(), pos: name.pos.into() }.into_pyobject(py)?.into_any())
+        },
+    }
+}
+
+fn to_py_value(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    let obj = match value {
+        Value::String(text) |
+        Value::Number(text) |
+        Value::Ident(text) => text.into_pyobject(py)?.into_any(),
+        Value::Bool(b) => b.into_pyobject(py)?.to_owned().into_any(),
+        // Replaced before the tree is returned
+        Value::Variable(name) => format!("{{{name}}}").into_pyobject(py)?.into_any(),
+        Value::Map(entries) => {
+            let dict = PyDict::new(py);
+            for (key, value) in entries {
+                dict.set_item(&key.node, to_py_value(py, value)?)?;
+            }
+            dict.into_any()
+        },
+    };
+
+    Ok(obj.unbind())
+}
+
+// Transpiles a page to HTML
+#[pyfunction]
+#[pyo3(signature = (source, *, filename=None, indent=4, minify=false, variables=None))]
+fn transpile(py: Python<'_>, source: &str, filename: Option<PathBuf>, indent: usize, minify: bool, variables: Option<&Bound<PyDict>>) -> PyResult<String> {
+    let opts = options(indent, minify, variables)?;
+    ::nhtml::transpile_with(source, filename.as_deref(), &opts)
+        .map_err(|err| to_py_err(py, &err))
+}
+
+// Parses a page into a list of `Tag`, `Text` and `Block`, with the variables
+// replaced
+#[pyfunction]
+#[pyo3(signature = (source, *, filename=None, variables=None))]
+fn parse(py: Python<'_>, source: &str, filename: Option<PathBuf>, variables: Option<&Bound<PyDict>>) -> PyResult<Py<PyList>> {
+    let opts = options(4, false, variables)?;
+    let elements = ::nhtml::parse(source, filename.as_deref(), &opts)
+        .map_err(|err| to_py_err(py, &err))?;
+
+    to_py_elements(py, &elements)
+}
+
+#[pymodule]
+#[pyo3(name = "nhtml")]
+fn nhtml_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(transpile, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add("NhtmlError", m.py().get_type::<NhtmlError>())?;
+    m.add_class::<Position>()?;
+    m.add_class::<Tag>()?;
+    m.add_class::<Attribute>()?;
+    m.add_class::<Spread>()?;
+    m.add_class::<Text>()?;
+    m.add_class::<Block>()?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+# Needs the module installed, for example with `maturin develop`
+import unittest
+
+import nhtml
+
+
+class TestTranspile(unittest.TestCase):
+    def test_transpile(self):
+        self.assertEqual(nhtml.transpile("p 'Hi';"), "<p>\n    Hi\n</p>\n")
+        self.assertEqual(nhtml.transpile("div { p 'Hi'; }", minify=True), "<div><p>Hi</p></div>")
+        self.assertEqual(nhtml.transpile("p 'Hi';", indent=2, minify=False), "<p>\n  Hi\n</p>\n")
+
+    def test_variables(self):
+        variables = {"name": "nhtml", "count": 3, "open": False, "base": {"class": "btn", "type": "button"}}
+        html = nhtml.transpile("button ...base open={open} data-n={count} '{{ name }}';", variables=variables)
+        self.assertEqual(html, '<button class="btn" type="button" data-n="3">\n    nhtml\n</button>\n')
+
+        with self.assertRaises(TypeError):
+            nhtml.transpile("p;", variables={"x": 1.5})
+
+    def test_errors(self):
+        with self.assertRaises(nhtml.NhtmlError) as cm:
+            nhtml.transpile("div {\n  p $;\n}", filename="index.nhtml")
+
+        err = cm.exception
+        self.assertIsInstance(err, ValueError)
+        self.assertEqual(err.code, "E0001")
+        self.assertEqual(err.message, "Invalid character '$'")
+        self.assertEqual(err.filename, "index.nhtml")
+        self.assertEqual((err.line, err.column, err.end_line, err.end_column), (2, 5, 2, 5))
+        self.assertTrue(str(err).startswith("error[E0001]: Invalid character '$'\n --> index.nhtml:2:5"))
+
+
+class TestParse(unittest.TestCase):
+    def test_parse(self):
+        elements = nhtml.parse("div class={a: true, b: false} {\n  img src='a.png' hidden;\n  'Hi'\n}\njs{ go() }")
+        self.assertEqual(len(elements), 2)
+
+        div, script = elements
+        self.assertIsInstance(div, nhtml.Tag)
+        self.assertEqual(div.name, "div")
+        self.assertEqual((div.pos.line, div.pos.column, div.pos.end_line, div.pos.end_column), (1, 1, 4, 1))
+        self.assertEqual((div.name_pos.line, div.name_pos.column, div.name_pos.end_column), (1, 1, 3))
+        self.assertEqual(div.attributes[0].value, {"a": True, "b": False})
+
+        img, text = div.children
+        self.assertEqual([(a.name, a.value) for a in img.attributes], [("src", "a.png"), ("hidden", True)])
+        self.assertEqual((img.attributes[0].pos.line, img.attributes[0].pos.column), (2, 7))
+        self.assertIsInstance(text, nhtml.Text)
+        self.assertEqual(text.text, "Hi")
+
+        self.assertIsInstance(script, nhtml.Block)
+        self.assertEqual(script.kind, "js")
+
+    def test_spread(self):
+        [tag] = nhtml.parse("a ...link;", variables={"link": {"href": "/"}})
+        self.assertIsInstance(tag.attributes[0], nhtml.Spread)
+        self.assertEqual(tag.attributes[0].name, "link")
+        self.assertEqual(tag.attributes[0].attributes, {"href": "/"})
+
+
+if __name__ == "__main__":
+    unittest.main()
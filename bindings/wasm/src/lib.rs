@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use nhtml::{config::Options, diagnostic::Diagnostic, render::{self, Style}};
+
+// What `transpile` returns to JavaScript: the HTML, or null if the page has
+// errors, and the diagnostics in the same form as `--error-format json`
+// with the text the command line would print added as `rendered`
+#[derive(Serialize)]
+struct Output {
+    html: Option<String>,
+    diagnostics: Vec<Value>,
+}
+
+// Errors point at `filename` if it is given
+#[wasm_bindgen]
+pub fn transpile(source: &str, filename: Option<String>) -> Result<JsValue, JsError> {
+    let output = transpile_output(source, filename.as_deref().map(Path::new));
+    output.serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|err| JsError::new(&err.to_string()))
+}
+
+fn transpile_output(source: &str, filename: Option<&Path>) -> Output {
+    match nhtml::transpile_with(source, filename, &Options::default()) {
+        Ok(html) => Output { html: Some(html), diagnostics: vec![] },
+        Err(err) => Output { html: None, diagnostics: vec![to_json(&Diagnostic::from_error(&err))] },
+    }
+}
+
+fn to_json(diag: &Diagnostic) -> Value {
+    let mut obj = diag.to_json();
+    obj["rendered"] = json!(render::render(diag, Style { color: false }));
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::transpile_output;
+
+    #[test]
+    fn test_transpile_output() {
+        let output = transpile_output("p 'Hi';", None);
+        assert_eq!(output.html.as_deref(), Some("<p>\n    Hi\n</p>\n"));
+        assert!(output.diagnostics.is_empty());
+
+        let output = transpile_output("div {\n  p $;\n}", Some(Path::new("index.nhtml")));
+        assert_eq!(output.html, None);
+
+        let diag = &output.diagnostics[0];
+        assert_eq!(diag["code"], "E0001");
+        assert_eq!(diag["file"], "index.nhtml");
+        assert_eq!((&diag["start_line"], &diag["start_column"]), (&2.into(), &5.into()));
+        assert!(diag["rendered"].as_str().unwrap().starts_with("error[E0001]: Invalid character '$'\n"));
+    }
+}
@@ -0,0 +1,18 @@
+// Run with `wasm-pack test --node bindings/wasm`
+#![cfg(target_arch = "wasm32")]
+
+use serde_json::Value;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn test_transpile() {
+    let output: Value = serde_wasm_bindgen::from_value(nhtml_wasm::transpile("p 'Hi';", None).unwrap()).unwrap();
+    assert_eq!(output["html"], "<p>\n    Hi\n</p>\n");
+    assert_eq!(output["diagnostics"], Value::Array(vec![]));
+
+    let output: Value = serde_wasm_bindgen::from_value(nhtml_wasm::transpile("p 'Hi", Some("index.nhtml".to_owned())).unwrap()).unwrap();
+    assert_eq!(output["html"], Value::Null);
+    assert_eq!(output["diagnostics"][0]["code"], "E0002");
+    assert_eq!(output["diagnostics"][0]["file"], "index.nhtml");
+    assert_eq!(output["diagnostics"][0]["start_line"], 1);
+}
@@ -0,0 +1,56 @@
+use serde_json::{json, Value};
+
+// Memory handed out by `alloc`. Every page is transformed in a new instance,
+// so nothing is ever freed.
+#[no_mangle]
+pub extern "C" fn alloc(len: u32) -> u32 {
+    let buf = vec![0u8; len as usize].leak();
+    buf.as_mut_ptr() as u32
+}
+
+#[no_mangle]
+pub extern "C" fn transform(ptr: u32, len: u32) -> u64 {
+    let input = unsafe { std::slice::from_raw_parts(ptr as *const u8, len as usize) };
+
+    let output = match serde_json::from_slice::<Value>(input) {
+        Ok(mut page) => {
+            if let Some(elements) = page.get_mut("elements") {
+                add_loading(elements);
+            }
+            page
+        },
+        Err(err) => json!({ "diagnostics": [{ "message": format!("Invalid page: {err}") }] }),
+    };
+
+    let output = serde_json::to_vec(&output).unwrap().leak();
+    ((output.as_ptr() as u64) << 32) | output.len() as u64
+}
+
+fn add_loading(elements: &mut Value) {
+    let Some(elements) = elements.as_array_mut() else {
+        return;
+    };
+
+    for element in elements {
+        if element["type"] != "tag" {
+            continue;
+        }
+
+        let is_img = element["ty"]["node"].as_str().is_some_and(|ty| ty.eq_ignore_ascii_case("img"));
+        let has_loading = element["attribs"].as_array().is_some_and(|attribs| {
+            attribs.iter().any(|attrib| attrib["name"]["node"] == "loading")
+        });
+
+        if is_img && !has_loading {
+            if let Some(attribs) = element["attribs"].as_array_mut() {
+                attribs.push(json!({
+                    "type": "named",
+                    "name": { "node": "loading" },
+                    "value": { "node": { "type": "string", "value": "lazy" } },
+                }));
+            }
+        }
+
+        add_loading(&mut element["body"]);
+    }
+}
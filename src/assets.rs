@@ -0,0 +1,77 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+
+// Mirrors a file that isn't a page into the output. Returns whether it was
+// copied, files that are already up to date are left alone.
+pub fn copy_asset(path: &Path, output: &Path, hard_link: bool) -> Result<bool> {
+    if is_fresh(path, output, hard_link) {
+        return Ok(false);
+    }
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if hard_link {
+        // Links can't replace an existing file, and fail across file systems
+        let _ = fs::remove_file(output);
+        if fs::hard_link(path, output).is_ok() {
+            return Ok(true);
+        }
+    }
+
+    fs::copy(path, output)
+        .context(format!("Failed to copy '{}' to '{}'", path.display(), output.display()))?;
+    Ok(true)
+}
+
+// Copies keep the modification time of when they were made, so a copy is
+// fresh if it is the same size and newer than the original. A hard link
+// always is once it exists.
+fn is_fresh(path: &Path, output: &Path, hard_link: bool) -> bool {
+    let (Ok(src), Ok(out)) = (fs::metadata(path), fs::metadata(output)) else {
+        return false;
+    };
+
+    if hard_link && same_file(&src, &out) {
+        return true;
+    }
+
+    match (src.modified(), out.modified()) {
+        (Ok(src_time), Ok(out_time)) => src.len() == out.len() && out_time >= src_time,
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &fs::Metadata, _: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::copy_asset;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn test_copy_and_link() {
+        let root = TempDir::new("assets");
+
+        let src = root.join("logo.svg");
+        std::fs::write(&src, "<svg></svg>").unwrap();
+
+        assert!(copy_asset(&src, &root.join("out/img/logo.svg"), false).unwrap());
+        assert!(!copy_asset(&src, &root.join("out/img/logo.svg"), false).unwrap());
+        assert_eq!(std::fs::read_to_string(root.join("out/img/logo.svg")).unwrap(), "<svg></svg>");
+
+        assert!(copy_asset(&src, &root.join("linked/logo.svg"), true).unwrap());
+        assert!(!copy_asset(&src, &root.join("linked/logo.svg"), true).unwrap());
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+
+use crate::{parser::{Attrib, Element, Value}, position::{Position, Spanned}, walk};
+
+// Attributes whose values are joined when they are set more than once, each
+// with the separator between the parts of its value. By default classes are
+// joined with spaces and style declarations with `;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeRules {
+    separators: BTreeMap<String, String>,
+}
+
+impl Default for MergeRules {
+    fn default() -> Self {
+        Self {
+            separators: BTreeMap::from([
+                ("class".to_owned(), " ".to_owned()),
+                ("style".to_owned(), ";".to_owned()),
+            ]),
+        }
+    }
+}
+
+impl MergeRules {
+    // Adds rules to the defaults. An empty separator removes the rule, so the
+    // attribute can only be set once.
+    pub fn new(rules: BTreeMap<String, String>) -> Self {
+        let mut merge = Self::default();
+        for (name, separator) in rules {
+            let name = name.to_ascii_lowercase();
+            if separator.is_empty() {
+                merge.separators.remove(&name);
+            } else {
+                merge.separators.insert(name, separator);
+            }
+        }
+
+        merge
+    }
+
+    pub fn separator(&self, name: &str) -> Option<&str> {
+        self.separators.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+// The boolean attributes of HTML, which are on when they are there at all
+const BOOLEAN: &[&str] = &[
+    "allowfullscreen", "async", "autofocus", "autoplay", "checked", "controls", "default",
+    "defer", "disabled", "formnovalidate", "hidden", "inert", "ismap", "itemscope", "loop",
+    "multiple", "muted", "nohref", "nomodule", "novalidate", "open", "playsinline", "readonly",
+    "required", "reversed", "selected", "shadowrootclonable", "shadowrootdelegatesfocus",
+    "shadowrootserializable",
+];
+
+// Whether `true` and `false` keep and leave out the attribute. Every other
+// attribute gets them as text, like `value="false"` or `aria-hidden="true"`.
+pub fn is_boolean(name: &str) -> bool {
+    BOOLEAN.contains(&name.to_ascii_lowercase().as_str())
+}
+
+// Where an attribute was set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    // At the position of the attribute
+    Explicit(Position),
+    // `...name` at the position of the name
+    Spread(String, Position),
+}
+
+// An attribute that was set more than once, where the last value is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    pub name: String,
+    pub first: Source,
+    pub last: Source,
+}
+
+// The attributes of a tag as they are emitted
+#[derive(Debug, Default)]
+pub struct Merged {
+    pub attribs: Vec<(String, Option<Value>)>,
+    pub overrides: Vec<Override>,
+}
+
+// Flattens spreads and turns `class` and `style` maps into strings.
+// Attributes with a merge rule are joined into one, leaving out parts that
+// are already there. Other attributes that are set more than once keep their
+// first position and take the last value.
+pub fn merge(attribs: &[Attrib], rules: &MergeRules) -> Merged {
+    let mut merged = Merged::default();
+    let mut sources: Vec<Source> = vec![];
+
+    let mut flattened = vec![];
+    for attrib in attribs {
+        match attrib {
+            Attrib::Named { name, value } => flattened.push((&name.node, value.as_ref().map(|v| &v.node), Source::Explicit(attrib.pos()))),
+            Attrib::Spread { name, attribs } => flattened.extend(attribs.iter()
+                .map(|(attr, value)| (attr, value.as_ref(), Source::Spread(name.node.clone(), name.pos)))),
+        }
+    }
+
+    for (name, value, source) in flattened {
+        let value = match value {
+            Some(Value::Map(entries)) => Some(from_map(name, entries)),
+            value => value.cloned(),
+        };
+
+        let Some(i) = merged.attribs.iter().position(|(attr, _)| attr.eq_ignore_ascii_case(name)) else {
+            merged.attribs.push((name.clone(), value));
+            sources.push(source);
+            continue;
+        };
+
+        let existing = &mut merged.attribs[i].1;
+        if let Some(separator) = rules.separator(name) {
+            *existing = join(existing, &value, separator);
+        } else {
+            *existing = value;
+            merged.overrides.push(Override {
+                name: name.clone(),
+                first: std::mem::replace(&mut sources[i], source.clone()),
+                last: source,
+            });
+        }
+    }
+
+    merged
+}
+
+// The first attribute in the page that is written more than once in the same
+// tag without a merge rule. Spreads are checked by the linter instead.
+pub fn duplicate(elements: &[Element], rules: &MergeRules) -> Option<Override> {
+    let mut found = None;
+    walk::walk(elements, &mut |element, _| {
+        if let (None, Element::Tag(tag)) = (&found, element) {
+            found = merge(&tag.attribs, rules).overrides.into_iter()
+                .find(|o| matches!((&o.first, &o.last), (Source::Explicit(_), Source::Explicit(_))));
+        }
+    });
+
+    found
+}
+
+// Classes with a value that isn't `false` or empty, or declarations for every
+// property that has a value
+fn from_map(name: &str, entries: &[(Spanned<String>, Spanned<Value>)]) -> Value {
+    let is_class = name.eq_ignore_ascii_case("class");
+
+    let parts: Vec<String> = entries.iter()
+        .filter_map(|(key, value)| {
+            let text = value.as_str();
+            match (is_class, &value.node) {
+                (_, Value::Bool(false)) => None,
+                (true, _) if text == Some("") => None,
+                (true, _) => Some(key.node.clone()),
+                (false, _) => text.map(|text| format!("{key}: {text}")),
+            }
+        })
+        .collect();
+
+    if parts.is_empty() {
+        return Value::Bool(false);
+    }
+
+    Value::String(parts.join(if is_class { " " } else { "; " }))
+}
+
+// Joins the parts of two values, leaving out duplicates. Values without text
+// have no parts. Separators other than whitespace are followed by a space,
+// like `color: red; width: 1em`.
+fn join(first: &Option<Value>, second: &Option<Value>, separator: &str) -> Option<Value> {
+    let mut parts: Vec<&str> = vec![];
+    for value in [first, second] {
+        let text = value.as_ref().and_then(Value::as_str).unwrap_or_default();
+        let split: Vec<&str> = if separator.trim().is_empty() {
+            text.split_whitespace().collect()
+        } else {
+            text.split(separator).map(str::trim).filter(|part| !part.is_empty()).collect()
+        };
+
+        for part in split {
+            if !parts.contains(&part) {
+                parts.push(part);
+            }
+        }
+    }
+
+    if parts.is_empty() {
+        return first.clone().or(second.clone());
+    }
+
+    let separator = match separator.trim() {
+        "" => separator.to_owned(),
+        separator => format!("{separator} "),
+    };
+    Some(Value::String(parts.join(&separator)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{merge, MergeRules, Source};
+    use crate::{parser::{Attrib, Value}, position::{Position, Spanned}};
+
+    #[test]
+    fn test_merge() {
+        let pos = Position { idx: 0, len: 0, start_ln: 1, start_cn: 1, end_ln: 1, end_cn: 1 };
+        let named = |name: &str, value: &str| (name.to_owned(), Some(Value::String(value.to_owned())));
+        let spanned = |value| Spanned::new(value, pos);
+        let attribs = vec![
+            Attrib::Spread {
+                name: spanned("base".to_owned()),
+                attribs: vec![named("type", "button"), named("class", "btn big"), named("style", "color: red;")],
+            },
+            Attrib::Named {
+                name: spanned("class".to_owned()),
+                value: Some(Spanned::new(Value::Map(vec![
+                    (spanned("big".to_owned()), Spanned::new(Value::Bool(true), pos)),
+                    (spanned("active".to_owned()), Spanned::new(Value::Bool(false), pos)),
+                    (spanned("wide".to_owned()), Spanned::new(Value::String("yes".to_owned()), pos)),
+                ]), pos)),
+            },
+            Attrib::Named {
+                name: spanned("style".to_owned()),
+                value: Some(Spanned::new(Value::Map(vec![
+                    (spanned("width".to_owned()), Spanned::new(Value::Number("100px".to_owned()), pos)),
+                ]), pos)),
+            },
+            Attrib::Named {
+                name: spanned("type".to_owned()),
+                value: Some(Spanned::new(Value::String("submit".to_owned()), pos)),
+            },
+        ];
+
+        let merged = merge(&attribs, &MergeRules::default());
+        assert_eq!(merged.attribs, vec![
+            named("type", "submit"),
+            named("class", "btn big wide"),
+            named("style", "color: red; width: 100px"),
+        ]);
+
+        assert_eq!(merged.overrides.len(), 1);
+        assert_eq!(merged.overrides[0].first, Source::Spread("base".to_owned(), pos));
+        assert_eq!(merged.overrides[0].last, Source::Explicit(pos));
+
+        // Without a rule for `class` the last one wins
+        let rules = MergeRules::new(BTreeMap::from([("class".to_owned(), String::new())]));
+        let merged = merge(&attribs, &rules);
+        assert_eq!(merged.attribs[1], named("class", "big wide"));
+        assert_eq!(merged.overrides.len(), 2);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}, sync::RwLock};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+
+// Name of the cache file kept in the output directory
+pub const CACHE_FILE: &str = ".nhtml-cache.json";
+
+// Bumped whenever the cache format or the generated output changes, so
+// caches written by other versions are discarded
+const CACHE_VERSION: &str = concat!("1-", env!("CARGO_PKG_VERSION"));
+
+// A built page, the output it was written to and the content hash of every
+// file it was built from, including its own source
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Page {
+    output: PathBuf,
+    deps: BTreeMap<PathBuf, u64>,
+}
+
+// The dependency graph of the last build together with content hashes, used
+// to skip pages whose inputs did not change and to find which pages have to
+// be rebuilt when a file changes. All paths are canonical. The cache can be
+// shared between the threads of a parallel build.
+#[derive(Debug)]
+pub struct BuildCache {
+    // Where the cache is saved, caches without a file are only kept in memory
+    file: Option<PathBuf>,
+    pages: RwLock<BTreeMap<PathBuf, Page>>,
+}
+
+impl BuildCache {
+    // Loads the cache from the output directory. A missing or unreadable
+    // cache is not an error, everything is simply rebuilt.
+    pub fn load(dir: &Path) -> Self {
+        let file = dir.join(CACHE_FILE);
+        let pages = std::fs::read_to_string(&file).ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+            .and_then(|value| parse_pages(&value))
+            .unwrap_or_default();
+
+        Self {
+            file: Some(file),
+            pages: RwLock::new(pages),
+        }
+    }
+
+    // The cache for a build, kept in the output directory. A single file is
+    // always built, so no cache file ends up next to it in the user's files.
+    pub fn for_output(input: &Path, output: &Path) -> Self {
+        if input.is_dir() {
+            return Self::load(output);
+        }
+
+        Self {
+            file: None,
+            pages: RwLock::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+
+        let pages: Map<String, Value> = self.pages.read().unwrap().iter()
+            .map(|(page, entry)| {
+                let deps: Map<String, Value> = entry.deps.iter()
+                    .map(|(dep, hash)| (dep.display().to_string(), json!(format!("{hash:016x}"))))
+                    .collect();
+
+                let value = json!({
+                    "output": entry.output.display().to_string(),
+                    "deps": deps,
+                });
+
+                (page.display().to_string(), value)
+            })
+            .collect();
+
+        let value = json!({
+            "version": CACHE_VERSION,
+            "pages": pages,
+        });
+
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(file, format!("{value:#}"))
+            .context(format!("Failed to write build cache '{}'", file.display()))
+    }
+
+    // Whether the page was built to this output before and none of the
+    // files it was built from changed since
+    pub fn is_fresh(&self, page: &Path, output: &Path) -> bool {
+        let (Ok(page), Ok(output)) = (page.canonicalize(), output.canonicalize()) else {
+            return false;
+        };
+
+        // Cloned so the lock isn't held while hashing
+        let Some(entry) = self.pages.read().unwrap().get(&page).cloned() else {
+            return false;
+        };
+
+        entry.output == output && entry.deps.iter()
+            .all(|(dep, hash)| hash_file(dep) == Some(*hash))
+    }
+
+    // Records that the page was built to the output from the given files
+    pub fn record(&self, page: &Path, output: &Path, deps: &[PathBuf]) -> Result<()> {
+        let mut hashes = BTreeMap::new();
+
+        for dep in deps {
+            let dep = dep.canonicalize()?;
+            let hash = hash_file(&dep)
+                .context(format!("Failed to read '{}'", dep.display()))?;
+            hashes.insert(dep, hash);
+        }
+
+        self.pages.write().unwrap().insert(page.canonicalize()?, Page {
+            output: output.canonicalize()?,
+            deps: hashes,
+        });
+
+        Ok(())
+    }
+
+    // Forgets a page, or every page in a directory
+    pub fn forget(&self, path: &Path) {
+        let path = normalize(path);
+        self.pages.write().unwrap().retain(|page, _| !page.starts_with(&path));
+    }
+
+    // Every page that was built from the file, which includes the file
+    // itself if it is a page
+    pub fn dependents(&self, file: &Path) -> Vec<PathBuf> {
+        let file = normalize(file);
+
+        self.pages.read().unwrap().iter()
+            .filter(|(_, entry)| entry.deps.contains_key(&file))
+            .map(|(page, _)| page.clone())
+            .collect()
+    }
+}
+
+// Canonicalizes paths that still exist, deleted files keep the path they were reported with
+fn normalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn parse_pages(value: &Value) -> Option<BTreeMap<PathBuf, Page>> {
+    if value["version"] != CACHE_VERSION {
+        return None;
+    }
+
+    let mut pages = BTreeMap::new();
+
+    for (page, entry) in value["pages"].as_object()? {
+        let mut deps = BTreeMap::new();
+        for (dep, hash) in entry["deps"].as_object()? {
+            let hash = u64::from_str_radix(hash.as_str()?, 16).ok()?;
+            deps.insert(PathBuf::from(dep), hash);
+        }
+
+        pages.insert(PathBuf::from(page), Page {
+            output: PathBuf::from(entry["output"].as_str()?),
+            deps,
+        });
+    }
+
+    Some(pages)
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    std::fs::read(path).ok().map(|bytes| fnv1a(&bytes))
+}
+
+// 64-bit FNV-1a. Stable across platforms and compiler versions, unlike the
+// hasher in the standard library, so hashes can be stored on disk.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildCache;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let root = TempDir::new("cache");
+        std::fs::create_dir_all(root.join("out")).unwrap();
+
+        let page = root.join("index.nhtml");
+        let partial = root.join("nav.nhtml");
+        let output = root.join("out/index.html");
+        std::fs::write(&page, "p 'Home';").unwrap();
+        std::fs::write(&partial, "nav;").unwrap();
+        std::fs::write(&output, "").unwrap();
+
+        let cache = BuildCache::load(&root.join("out"));
+        assert!(!cache.is_fresh(&page, &output));
+
+        cache.record(&page, &output, &[page.clone(), partial.clone()]).unwrap();
+        cache.save().unwrap();
+
+        let cache = BuildCache::load(&root.join("out"));
+        assert!(cache.is_fresh(&page, &output));
+        assert_eq!(cache.dependents(&partial), [page.canonicalize().unwrap()]);
+
+        std::fs::write(&partial, "nav { a; }").unwrap();
+        assert!(!cache.is_fresh(&page, &output));
+
+        // Single files are built without a cache file
+        let cache = BuildCache::for_output(&page, &root.join("index.html"));
+        cache.record(&page, &output, std::slice::from_ref(&page)).unwrap();
+        cache.save().unwrap();
+        assert!(cache.is_fresh(&page, &output));
+        assert!(!root.join(super::CACHE_FILE).exists());
+    }
+
+    #[test]
+    fn test_forget_directory() {
+        let root = TempDir::new("cache-forget");
+        std::fs::create_dir_all(root.join("blog")).unwrap();
+
+        let cache = BuildCache::load(&root.join("out"));
+        for page in ["index.nhtml", "blog/one.nhtml", "blog/two.nhtml"] {
+            std::fs::write(root.join(page), "p;").unwrap();
+            cache.record(&root.join(page), &root.join(page), &[root.join(page)]).unwrap();
+        }
+
+        // Pages in a deleted directory are forgotten along with it
+        std::fs::remove_dir_all(root.join("blog")).unwrap();
+        cache.forget(&root.join("blog"));
+        assert!(cache.dependents(&root.join("blog/one.nhtml")).is_empty());
+        assert!(cache.dependents(&root.join("blog/two.nhtml")).is_empty());
+        assert_eq!(cache.dependents(&root.join("index.nhtml")), [root.join("index.nhtml")]);
+    }
+}
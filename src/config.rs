@@ -0,0 +1,392 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+use crate::{attributes::MergeRules, check_extension, emitter::EmitOptions, lint::{Level, LintConfig}, transform::{Transform, Transforms}, variables::Variable};
+
+pub const CONFIG_FILE: &str = "nhtml.toml";
+
+// Options a single page is transpiled with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    pub emit: EmitOptions,
+    // How attributes that are set more than once are joined
+    pub merge: MergeRules,
+    pub variables: BTreeMap<String, Variable>,
+    // Passes run over the page before it is emitted
+    pub transforms: Transforms,
+}
+
+impl Options {
+    // Adds a transform that runs after the ones already added
+    pub fn with_transform(mut self, transform: impl Transform + 'static) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+}
+
+// The contents of nhtml.toml as written
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    build: BuildSection,
+    assets: AssetsSection,
+    emit: EmitSection,
+    variables: BTreeMap<String, Variable>,
+    lint: BTreeMap<String, Level>,
+    attributes: AttributesSection,
+    // WebAssembly transforms, run in the order they are listed
+    plugins: Vec<PluginSection>,
+    directories: BTreeMap<PathBuf, DirectorySection>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BuildSection {
+    source: Option<PathBuf>,
+    output: Option<PathBuf>,
+    ignore: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AssetsSection {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    hard_link: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PluginSection {
+    // Used in errors from the plugin
+    name: String,
+    path: PathBuf,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AttributesSection {
+    // Separators of attributes that may be set more than once
+    merge: BTreeMap<String, String>,
+}
+
+// Emitter options that are only set where they differ from the defaults or
+// from a parent directory
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct EmitSection {
+    indent: Option<usize>,
+    minify: Option<bool>,
+}
+
+impl EmitSection {
+    fn apply(&self, opts: &mut EmitOptions) {
+        if let Some(indent) = self.indent {
+            opts.indent = indent;
+        }
+
+        if let Some(minify) = self.minify {
+            opts.minify = minify;
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DirectorySection {
+    emit: EmitSection,
+    variables: BTreeMap<String, Variable>,
+}
+
+// Project settings from nhtml.toml. Paths, ignore globs and directory
+// overrides are relative to the directory the file is in.
+#[derive(Debug)]
+pub struct Config {
+    pub file: Option<PathBuf>,
+    pub source: PathBuf,
+    pub output: PathBuf,
+    // Hard link assets into the output instead of copying them
+    pub hard_link: bool,
+    pub lint: LintConfig,
+    // The files of the plugins every page goes through
+    pub plugins: Vec<PathBuf>,
+    // Canonical, so it can be compared with canonical page paths
+    root: PathBuf,
+    ignore: GlobSet,
+    // Assets are copied if they match `include`, or it is empty, and don't match `exclude`
+    include: GlobSet,
+    exclude: GlobSet,
+    options: Options,
+    // Sorted so parent directories are applied before their children
+    directories: Vec<(PathBuf, DirectorySection)>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            file: None,
+            source: PathBuf::from("src"),
+            output: PathBuf::from("out"),
+            hard_link: false,
+            lint: LintConfig::default(),
+            plugins: vec![],
+            root: PathBuf::from("."),
+            ignore: GlobSet::empty(),
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+            options: Options::default(),
+            directories: vec![],
+        }
+    }
+}
+
+impl Config {
+    // Looks for nhtml.toml in the directory and every parent of it
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        let dir = dir.canonicalize()
+            .context(format!("Failed to find '{}'", dir.display()))?;
+
+        for dir in dir.ancestors() {
+            let file = dir.join(CONFIG_FILE);
+            if file.is_file() {
+                return Self::load(&file).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn load(file: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(file)
+            .context(format!("Failed to read '{}'", file.display()))?;
+        let root = file.parent().unwrap_or(Path::new("."));
+
+        let mut config = Self::parse(&text, root)
+            .context(format!("Failed to load '{}'", file.display()))?;
+        config.file = Some(file.to_path_buf());
+        Ok(config)
+    }
+
+    fn parse(text: &str, root: &Path) -> Result<Self> {
+        let parsed: ConfigFile = toml::from_str(text)?;
+
+        let mut options = Options::default();
+        parsed.emit.apply(&mut options.emit);
+        options.merge = MergeRules::new(parsed.attributes.merge);
+        options.variables = parsed.variables;
+        load_plugins(&mut options, &parsed.plugins, root)?;
+
+        let mut directories: Vec<_> = parsed.directories.into_iter().collect();
+        directories.sort_by_key(|(dir, _)| dir.components().count());
+
+        Ok(Self {
+            file: None,
+            source: root.join(parsed.build.source.unwrap_or_else(|| "src".into())),
+            output: root.join(parsed.build.output.unwrap_or_else(|| "out".into())),
+            hard_link: parsed.assets.hard_link,
+            lint: LintConfig::new(parsed.lint)?,
+            plugins: parsed.plugins.iter().map(|plugin| root.join(&plugin.path)).collect(),
+            root: root.canonicalize().unwrap_or_else(|_| root.to_path_buf()),
+            ignore: glob_set(&parsed.build.ignore)?,
+            include: glob_set(&parsed.assets.include)?,
+            exclude: glob_set(&parsed.assets.exclude)?,
+            options,
+            directories,
+        })
+    }
+
+    // Whether a file or directory matches one of the ignore globs
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.matches(&self.ignore, path)
+    }
+
+    // Whether a file that isn't a page is copied to the output. Only looks
+    // at the path, so it also works for files that were deleted.
+    pub fn is_asset(&self, path: &Path) -> bool {
+        !check_extension(path, "nhtml") &&
+            !self.is_ignored(path) &&
+            (self.include.is_empty() || self.matches(&self.include, path)) &&
+            !self.matches(&self.exclude, path)
+    }
+
+    // The options for a page, with the overrides of every directory it is in
+    // applied on top of the project wide options
+    pub fn options_for(&self, page: &Path) -> Options {
+        let mut options = self.options.clone();
+        let Some(page) = self.relative(page) else {
+            return options;
+        };
+
+        for (dir, section) in &self.directories {
+            if page.starts_with(dir) {
+                section.emit.apply(&mut options.emit);
+                options.variables.extend(section.variables.clone());
+            }
+        }
+
+        options
+    }
+
+    // Globs are matched against the path relative to the project, or the
+    // full path for files outside of it
+    fn matches(&self, globs: &GlobSet, path: &Path) -> bool {
+        match self.relative(path) {
+            Some(relative) => globs.is_match(relative),
+            None => globs.is_match(path),
+        }
+    }
+
+    // Deleted files can't be canonicalized, they are only made absolute so
+    // they still match
+    fn relative(&self, path: &Path) -> Option<PathBuf> {
+        let path = path.canonicalize().or_else(|_| std::path::absolute(path)).ok()?;
+        path.strip_prefix(&self.root).ok().map(Path::to_path_buf)
+    }
+}
+
+#[cfg(feature = "plugins")]
+fn load_plugins(options: &mut Options, plugins: &[PluginSection], root: &Path) -> Result<()> {
+    for plugin in plugins {
+        options.transforms.push(crate::plugin::Plugin::load(&plugin.name, &root.join(&plugin.path))?);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "plugins"))]
+fn load_plugins(_options: &mut Options, plugins: &[PluginSection], _root: &Path) -> Result<()> {
+    if let Some(plugin) = plugins.first() {
+        anyhow::bail!("Plugin '{}' needs nhtml to be built with the `plugins` feature", plugin.name);
+    }
+
+    Ok(())
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut globs = GlobSetBuilder::new();
+    for pattern in patterns {
+        globs.add(Glob::new(pattern).context(format!("Invalid glob pattern '{pattern}'"))?);
+    }
+
+    Ok(globs.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Config;
+    use crate::{temp_dir::TempDir, variables::Variable};
+
+    #[test]
+    fn test_directory_overrides() {
+        let root = TempDir::new("config");
+        std::fs::create_dir_all(root.join("src/blog/2023")).unwrap();
+        std::fs::write(root.join("src/index.nhtml"), "").unwrap();
+        std::fs::write(root.join("src/blog/2023/post.nhtml"), "").unwrap();
+        std::fs::write(root.join("src/blog/_draft.nhtml"), "").unwrap();
+
+        let config = Config::parse(r#"
+            [build]
+            output = "dist"
+            ignore = ["**/_*.nhtml"]
+
+            [emit]
+            indent = 2
+
+            [variables]
+            site = "Example"
+            section = "Home"
+            button = { tabindex = 0, disabled = false }
+
+            [lint]
+            alt-text = "off"
+
+            [attributes]
+            merge = { rel = " ", style = "" }
+
+            [directories."src/blog/2023".emit]
+            indent = 8
+
+            [directories."src/blog"]
+            emit = { minify = true }
+            variables = { section = "Blog" }
+        "#, &root).unwrap();
+
+        assert_eq!(config.output, root.join("dist"));
+        assert!(config.is_ignored(&root.join("src/blog/_draft.nhtml")));
+        assert!(!config.is_ignored(&root.join("src/index.nhtml")));
+
+        let index = config.options_for(&root.join("src/index.nhtml"));
+        assert_eq!((index.emit.indent, index.emit.minify), (2, false));
+        assert_eq!(index.merge.separator("rel"), Some(" "));
+        assert_eq!(index.merge.separator("class"), Some(" "));
+        assert_eq!(index.merge.separator("style"), None);
+        assert_eq!(index.variables["section"], Variable::Text("Home".to_owned()));
+
+        let post = config.options_for(&root.join("src/blog/2023/post.nhtml"));
+        assert_eq!((post.emit.indent, post.emit.minify), (8, true));
+        assert_eq!(post.variables["section"], Variable::Text("Blog".to_owned()));
+        assert_eq!(post.variables["site"], Variable::Text("Example".to_owned()));
+        assert_eq!(post.variables["button"], Variable::Attributes(BTreeMap::from([
+            ("tabindex".to_owned(), Variable::Number(0)),
+            ("disabled".to_owned(), Variable::Bool(false)),
+        ])));
+
+        assert!(Config::parse("[build]\nsrc = 'x'", &root).is_err());
+        assert!(Config::parse("[lint]\nalt = 'off'", &root).is_err());
+    }
+
+    #[test]
+    fn test_assets() {
+        let root = TempDir::new("config-assets");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/logo.png"), "").unwrap();
+
+        let config = Config::parse(r#"
+            [assets]
+            exclude = ["**/*.psd"]
+        "#, &root).unwrap();
+
+        assert!(config.is_asset(&root.join("src/logo.png")));
+        assert!(config.is_asset(&root.join("src/deleted.svg")));
+        assert!(!config.is_asset(&root.join("src/logo.psd")));
+        assert!(!config.is_asset(&root.join("src/index.nhtml")));
+
+        // Relative globs match files that were deleted too
+        let config = Config::parse(r#"
+            [assets]
+            include = ["src/static/**"]
+            exclude = ["src/static/drafts/**"]
+        "#, &root).unwrap();
+
+        assert!(config.is_asset(&root.join("src/static/deleted.css")));
+        assert!(!config.is_asset(&root.join("src/static/drafts/deleted.css")));
+        assert!(!config.is_asset(&root.join("src/logo.png")));
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_plugins() {
+        let root = TempDir::new("config-plugins");
+        std::fs::write(root.join("one.wasm"), include_bytes!("../tests/fixtures/echo.wasm")).unwrap();
+        std::fs::write(root.join("two.wasm"), include_bytes!("../tests/fixtures/echo.wasm")).unwrap();
+
+        // Plugins run in the order they are listed, not by name
+        let config = Config::parse(r#"
+            [[plugins]]
+            name = "zebra"
+            path = "two.wasm"
+
+            [[plugins]]
+            name = "aardvark"
+            path = "one.wasm"
+        "#, &root).unwrap();
+        assert_eq!(config.plugins, vec![root.join("two.wasm"), root.join("one.wasm")]);
+
+        assert!(Config::parse("[[plugins]]\npath = 'one.wasm'", &root).is_err());
+    }
+}
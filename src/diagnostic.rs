@@ -0,0 +1,305 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::{parser::ParseError, position::Position, scanner::ScanError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+// A span of source code pointed at by a diagnostic. The primary label marks
+// where the problem is, secondary labels point at related locations.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub pos: Position,
+    pub message: Option<String>,
+    pub primary: bool,
+}
+
+// A proposed fix. The text covered by `pos` is replaced with `replacement`,
+// or if `insert` is set the replacement is inserted right before `pos`.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub message: String,
+    pub pos: Position,
+    pub replacement: String,
+    pub insert: bool,
+}
+
+// A single problem found while transpiling, detached from the error type
+// that produced it so it can be rendered for humans or machines.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: Option<PathBuf>,
+    pub source: Option<String>,
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn error(code: Option<&'static str>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: Option<&'static str>, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn new(severity: Severity, code: Option<&'static str>, message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            source: None,
+            severity,
+            code,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+            suggestions: vec![],
+        }
+    }
+
+    pub fn from_error(err: &anyhow::Error) -> Self {
+        if let Some(e) = err.downcast_ref::<ScanError>() {
+            return e.to_diagnostic();
+        }
+
+        if let Some(e) = err.downcast_ref::<ParseError>() {
+            return e.to_diagnostic();
+        }
+
+        Self::error(None, format!("{err:#}"))
+    }
+
+    pub fn with_path(mut self, path: Option<&Path>) -> Self {
+        self.path = path.map(Path::to_path_buf);
+        self
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_owned());
+        self
+    }
+
+    pub fn with_label(mut self, pos: Position, message: Option<&str>) -> Self {
+        self.labels.push(Label { pos, message: message.map(String::from), primary: true });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, pos: Position, message: &str) -> Self {
+        self.labels.push(Label { pos, message: Some(message.to_owned()), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_owned());
+        self
+    }
+
+    pub fn with_replacement(mut self, message: &str, pos: Position, replacement: &str) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.to_owned(),
+            pos,
+            replacement: replacement.to_owned(),
+            insert: false,
+        });
+        self
+    }
+
+    pub fn with_insertion(mut self, message: &str, pos: Position, text: &str) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.to_owned(),
+            pos,
+            replacement: text.to_owned(),
+            insert: true,
+        });
+        self
+    }
+
+    // Position of the first primary label
+    pub fn pos(&self) -> Option<Position> {
+        self.labels.iter()
+            .find(|l| l.primary)
+            .map(|l| l.pos)
+    }
+
+    // A flat JSON object, meant to be printed one per line
+    pub fn to_json(&self) -> Value {
+        let mut obj = json!({
+            "file": self.path.as_ref().map(|p| p.display().to_string()),
+            "severity": self.severity.as_str(),
+            "code": self.code,
+            "message": self.message,
+        });
+
+        if let Some(pos) = self.pos() {
+            obj["start_line"] = json!(pos.start_ln);
+            obj["start_column"] = json!(pos.start_cn);
+            obj["end_line"] = json!(pos.end_ln);
+            obj["end_column"] = json!(pos.end_cn);
+        }
+
+        obj["labels"] = self.labels.iter()
+            .filter(|l| !l.primary)
+            .map(|l| json!({
+                "message": l.message,
+                "start_line": l.pos.start_ln,
+                "start_column": l.pos.start_cn,
+                "end_line": l.pos.end_ln,
+                "end_column": l.pos.end_cn,
+            }))
+            .collect();
+
+        obj["notes"] = json!(self.notes);
+
+        obj["suggestions"] = self.suggestions.iter()
+            .map(|s| json!({
+                "message": s.message,
+                "replacement": s.replacement,
+                "insert": s.insert,
+                "start_line": s.pos.start_ln,
+                "start_column": s.pos.start_cn,
+                "end_line": s.pos.end_ln,
+                "end_column": s.pos.end_cn,
+            }))
+            .collect();
+
+        obj
+    }
+
+    fn to_sarif_result(&self) -> Value {
+        let mut text = self.message.clone();
+        for note in &self.notes {
+            text.push_str(&format!("\nnote: {note}"));
+        }
+
+        let mut result = json!({
+            "ruleId": self.code,
+            "level": self.severity.as_str(),
+            "message": { "text": text },
+        });
+
+        if self.path.is_some() || self.pos().is_some() {
+            let location = self.sarif_location(self.pos().as_ref());
+            result["locations"] = json!([location]);
+        }
+
+        let related: Vec<Value> = self.labels.iter()
+            .filter(|l| !l.primary)
+            .map(|l| {
+                let mut location = self.sarif_location(Some(&l.pos));
+                location["message"] = json!({ "text": l.message });
+                location
+            })
+            .collect();
+
+        if !related.is_empty() {
+            result["relatedLocations"] = json!(related);
+        }
+
+        result
+    }
+
+    fn sarif_location(&self, pos: Option<&Position>) -> Value {
+        let mut location = json!({});
+
+        if let Some(path) = &self.path {
+            location["artifactLocation"] = json!({ "uri": path.display().to_string() });
+        }
+
+        // SARIF end columns are exclusive
+        if let Some(pos) = pos {
+            location["region"] = json!({
+                "startLine": pos.start_ln,
+                "startColumn": pos.start_cn,
+                "endLine": pos.end_ln,
+                "endColumn": pos.end_cn + 1,
+            });
+        }
+
+        json!({ "physicalLocation": location })
+    }
+}
+
+// Wraps diagnostics in a SARIF 2.1.0 log with a single run
+pub fn to_sarif(diagnostics: &[Diagnostic]) -> Value {
+    let results: Vec<Value> = diagnostics.iter()
+        .map(Diagnostic::to_sarif_result)
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "nhtml",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/Zugeo0/nhtml",
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Diagnostic, to_sarif};
+    use crate::transpile;
+
+    #[test]
+    fn test_scan_error_to_json() {
+        let err = transpile("p $;").unwrap_err();
+        let diag = Diagnostic::from_error(&err).with_path(Some(Path::new("index.nhtml")));
+        let json = diag.to_json();
+
+        assert_eq!(json["file"], "index.nhtml");
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["code"], "E0001");
+        assert_eq!(json["message"], "Invalid character '$'");
+        assert_eq!(json["start_line"], 1);
+        assert_eq!(json["start_column"], 3);
+        assert_eq!(json["end_column"], 3);
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let err = transpile("p 'Café' $;").unwrap_err();
+        let json = Diagnostic::from_error(&err).to_json();
+
+        assert_eq!(json["start_column"], 10);
+        assert_eq!(json["end_column"], 10);
+    }
+
+    #[test]
+    fn test_parse_error_to_sarif() {
+        let err = transpile("div {").unwrap_err();
+        let diag = Diagnostic::from_error(&err);
+        let sarif = to_sarif(&[diag]);
+        let result = &sarif["runs"][0]["results"][0];
+
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(result["ruleId"], "E0008");
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["message"]["text"], "Unclosed block");
+        assert_eq!(result["relatedLocations"][0]["physicalLocation"]["region"]["startColumn"], 5);
+    }
+}
@@ -1,61 +1,97 @@
-use crate::parser::{Element, Tag};
+use crate::{attributes::{self, MergeRules}, parser::{Element, Tag, Value}};
 
-pub fn emit_html(elements: Vec<Element>) -> String {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmitOptions {
+    // Spaces per nesting level
+    pub indent: usize,
+    // Emit everything on one line without indentation
+    pub minify: bool,
+}
+
+impl Default for EmitOptions {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            minify: false,
+        }
+    }
+}
+
+impl EmitOptions {
+    fn indent_str(&self, indent: usize) -> String {
+        if self.minify {
+            return String::new();
+        }
+
+        " ".repeat(self.indent * indent)
+    }
+
+    fn newline(&self) -> &'static str {
+        if self.minify { "" } else { "\n" }
+    }
+}
+
+// Attributes that are set more than once are joined by the merge rules
+pub fn emit_html(elements: Vec<Element>, opts: &EmitOptions, merge: &MergeRules) -> String {
     let mut html = String::new();
 
     for element in &elements {
-        emit_element(&mut html, element, 0)
+        emit_element(&mut html, element, 0, opts, merge)
     }
 
     html
 }
 
-fn emit_element(html: &mut String, element: &Element, indent: usize) {
+fn emit_element(html: &mut String, element: &Element, indent: usize, opts: &EmitOptions, merge: &MergeRules) {
+    let nl = opts.newline();
+
     match element {
-        Element::Tag(tag) => emit_tag(html, tag, indent),
-        Element::Text(text) => html.push_str(&format!("{}{}\n", indent_str(indent), text)),
-        Element::HTML(raw_html) => html.push_str(&format!("{}{}\n", indent_str(indent), raw_html)),
-        Element::Js(raw_js) => html.push_str(&format!("{}\n", raw_js)),
-        Element::Css(raw_css) => html.push_str(&format!("{}\n", raw_css)),
+        Element::Tag(tag) => emit_tag(html, tag, indent, opts, merge),
+        Element::Text(text) => html.push_str(&format!("{}{}{nl}", opts.indent_str(indent), text)),
+        Element::Html(raw_html) => html.push_str(&format!("{}{}{nl}", opts.indent_str(indent), raw_html)),
+        Element::Js(raw_js) => html.push_str(&format!("{}{nl}", raw_js)),
+        Element::Css(raw_css) => html.push_str(&format!("{}{nl}", raw_css)),
     }
 }
 
-fn emit_tag(html: &mut String, tag: &Tag, indent: usize) {
-    html.push_str(&format!("{}<{}", indent_str(indent), tag.ty));
+fn emit_tag(html: &mut String, tag: &Tag, indent: usize, opts: &EmitOptions, merge: &MergeRules) {
+    html.push_str(&format!("{}<{}", opts.indent_str(indent), tag.ty));
 
-    for (name, value) in &tag.attribs {
-        emit_attrib(html, name.clone(), value.clone());
+    for (name, value) in &attributes::merge(&tag.attribs, merge).attribs {
+        emit_attrib(html, name, value.as_ref());
     }
 
-    let inline = tag.ty == "meta" || tag.ty == "link";
+    let inline = *tag.ty == "meta" || *tag.ty == "link";
 
-    html.push_str(">");
+    html.push('>');
 
-    if inline || tag.body.len() > 0 {
-        html.push('\n');
+    if inline || !tag.body.is_empty() {
+        html.push_str(opts.newline());
     }
 
     for element in &tag.body {
-        emit_element(html, element, indent + 1);
+        emit_element(html, element, indent + 1, opts, merge);
     }
 
-    if tag.body.len() > 0 {
-        html.push_str(&indent_str(indent));
+    if !tag.body.is_empty() {
+        html.push_str(&opts.indent_str(indent));
     }
 
     if !inline {
-        html.push_str(&format!("</{}>\n", tag.ty));
+        html.push_str(&format!("</{}>{}", tag.ty, opts.newline()));
     }
 }
 
-fn emit_attrib(html: &mut String, name: String, value: Option<String>) {
-    html.push_str(&format!(" {name}"));
-
-    if let Some(val) = value {
-        html.push_str(&format!("={val}"));
+// Values are always emitted in double quotes, whichever quotes they were
+// written with
+fn emit_attrib(html: &mut String, name: &str, value: Option<&Value>) {
+    match value {
+        Some(Value::Bool(b)) if !attributes::is_boolean(name) => html.push_str(&format!(" {name}=\"{b}\"")),
+        Some(Value::Bool(false)) => {},
+        None | Some(Value::Bool(true)) => html.push_str(&format!(" {name}")),
+        Some(value) => {
+            let text = value.as_str().unwrap_or_default().replace('"', "&quot;");
+            html.push_str(&format!(" {name}=\"{text}\""));
+        },
     }
 }
-
-fn indent_str(indent: usize) -> String {
-    "    ".repeat(indent)
-}
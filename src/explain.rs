@@ -0,0 +1,424 @@
+// Long form descriptions of every error code and lint rule, printed by
+// `nhtml explain`.
+// Codes are stable: once assigned, a code is never reused for a different error.
+
+const E0001: &str = r#"An invalid character was found outside of a string, comment or raw block.
+
+Element and attribute names follow the XML naming rules: they start with a
+letter, `_`, `:`, `-` or `@`, and may also contain digits and `.` after the
+first character. Letters outside of ASCII are allowed. Any other character
+has to be part of a quoted string.
+
+Erroneous code example:
+
+    p $price;
+
+Wrap the text in quotes to turn it into a text element:
+
+    p '$price';
+"#;
+
+const E0002: &str = r#"A string was opened but never closed.
+
+Strings may be delimited by either `'` or `"`, and must end with the same
+character they started with.
+
+Erroneous code example:
+
+    title 'My page;
+
+Close the string with the opening delimiter:
+
+    title 'My page';
+"#;
+
+const E0003: &str = r#"A raw HTML block was opened with `<` but never closed.
+
+Raw HTML is copied to the output as-is and ends at the matching `>`. Nested
+`<` and `>` pairs are allowed, but every `<` needs a matching `>`.
+
+Erroneous code example:
+
+    <!DOCTYPE html
+
+Close the raw HTML with `>`:
+
+    <!DOCTYPE html>
+"#;
+
+const E0004: &str = r#"A `js{` block was opened but its braces are not balanced.
+
+The contents of a `js{ ... }` block are copied to the output as-is and end at
+the matching `}`.
+
+Erroneous code example:
+
+    script js{
+        function hello() {
+            console.log('hello');
+    }
+
+Balance the braces inside the block:
+
+    script js{
+        function hello() {
+            console.log('hello');
+        }
+    }
+"#;
+
+const E0005: &str = r#"A `css{` block was opened but its braces are not balanced.
+
+The contents of a `css{ ... }` block are copied to the output as-is and end at
+the matching `}`.
+
+Erroneous code example:
+
+    style css{
+        body { margin: 0;
+    }
+
+Balance the braces inside the block:
+
+    style css{
+        body { margin: 0; }
+    }
+"#;
+
+const E0006: &str = r#"The parser found a token it did not expect at this point.
+
+The message tells which token was expected instead. A common cause is an
+`=` without an attribute value after it, or a text element that is not
+followed by `;`.
+
+Erroneous code example:
+
+    input value=;
+
+Give the attribute a value, or leave out the `=`:
+
+    input value='';
+"#;
+
+const E0007: &str = r#"An element was expected but something else was found.
+
+A tag is followed by its body: either `;`, a string, a block in `{ }`, or
+exactly one nested element.
+
+Erroneous code example:
+
+    div }
+
+Give the tag a body:
+
+    div { }
+"#;
+
+const E0008: &str = r#"A block opened with `{` was never closed.
+
+Every `{` that starts the body of a tag needs a matching `}`. The error points
+at the end of the input, where the parser was still expecting the `}`.
+
+Erroneous code example:
+
+    body {
+        div {
+            p 'Hello';
+    }
+
+Close every block that was opened:
+
+    body {
+        div {
+            p 'Hello';
+        }
+    }
+"#;
+
+const E0009: &str = r#"An attribute value references a variable that is not set.
+
+Attribute values written as `{name}` are replaced by the variable `name` from
+the [variables] section of nhtml.toml, or from the section of a directory the
+page is in. Unlike `{{ name }}` in strings, which is left as-is when the
+variable is missing, a reference has to resolve.
+
+Erroneous code example:
+
+    a href={home} 'Home';
+
+Set the variable in nhtml.toml:
+
+    [variables]
+    home = "/index.html"
+"#;
+
+const E0010: &str = r#"A variable was used in a way that doesn't fit its value.
+
+Variables set to a table in nhtml.toml are sets of attributes, which can only
+be spread into a tag with `...name`. Other variables are single values, used
+as `{name}` or in a `class` or `style` map.
+
+Erroneous code example:
+
+    [variables]
+    primary = { type = "submit", class = "btn" }
+
+    button class={primary} 'Send';
+
+Spread the attributes into the tag:
+
+    button ...primary 'Send';
+"#;
+
+const E0011: &str = r#"An attribute was set more than once in the same tag.
+
+Browsers only keep one of the values, so the other one is silently lost.
+Attributes with a merge rule are joined instead: every `class` of a tag is
+merged into one, as is every `style`. Rules for other attributes are set in
+nhtml.toml, with the separator between their parts.
+
+Erroneous code example:
+
+    a href='/' href='/home' 'Home';
+
+Remove one of them:
+
+    a href='/home' 'Home';
+
+Or join the values of an attribute like `rel`:
+
+    [attributes]
+    merge = { rel = " " }
+"#;
+
+const E0012: &str = r#"A transform or plugin reported a problem with the page.
+
+Transforms run over the page after it is parsed and before it is written.
+They can reject parts of a page they don't support, pointing at where the
+problem is. What is allowed depends on the transform, so its documentation
+describes how to fix the error.
+
+Erroneous code example, with a plugin that only allows images with `alt`
+text:
+
+    [[plugins]]
+    name = "images"
+    path = "plugins/images.wasm"
+
+    img src='logo.png';
+
+Add what the transform asks for:
+
+    img src='logo.png' alt='Logo';
+"#;
+
+// Lint rules are explained under their names, which lint diagnostics use as
+// their code
+
+const CONTENT_MODEL: &str = r#"An element is inside a parent that doesn't allow it.
+
+HTML limits what some elements can contain. Lists only hold `li`, tables
+only hold rows and their groups, and a paragraph ends at the first block
+element like `div`. Browsers repair these pages by moving elements around,
+so the page that is shown is not the one that was written.
+
+Example of code the rule reports:
+
+    ul {
+        p 'Fruit';
+        li 'Apple';
+    }
+
+Move the element to where it is allowed:
+
+    p 'Fruit';
+    ul {
+        li 'Apple';
+    }
+"#;
+
+const NESTED_INTERACTIVE: &str = r#"A link or button is inside another link or button.
+
+Interactive elements can't contain each other. Browsers split a nested link
+out of its parent, and it is unclear which one a click on the inner one
+activates, for people and for screen readers.
+
+Example of code the rule reports:
+
+    a href='/post' {
+        h2 'Title';
+        a href='/author' 'Author';
+    }
+
+Keep them next to each other:
+
+    a href='/post' {
+        h2 'Title';
+    }
+    a href='/author' 'Author';
+"#;
+
+const REQUIRED_ATTRIBUTES: &str = r#"An element is missing an attribute it doesn't work without.
+
+Some elements do nothing useful without certain attributes, like an `img`
+without `src` or a `link` without `href`. Where there are alternatives, like
+`src` or `srcdoc` on an `iframe`, one of them is enough. Attributes from a
+spread count, and boolean attributes set to `false` don't.
+
+Example of code the rule reports:
+
+    img alt='Logo';
+
+Add the attribute:
+
+    img src='logo.png' alt='Logo';
+"#;
+
+const DUPLICATE_ID: &str = r#"An `id` is used by more than one element in the page.
+
+Ids have to be unique. Links to `#id`, `label for` and scripts only find the
+first element with an id, so the others are silently ignored.
+
+Example of code the rule reports:
+
+    section id='intro' 'Welcome';
+    section id='intro' 'Getting started';
+
+Give every element its own id:
+
+    section id='intro' 'Welcome';
+    section id='start' 'Getting started';
+"#;
+
+const DUPLICATE_ATTRIBUTE: &str = r#"An attribute is set more than once, and only the last value is used.
+
+Attributes written twice in a tag are error E0011. This rule reports the
+ones that come together from a spread and the tag, or from two spreads,
+which is allowed so a spread can provide defaults. Attributes with a merge
+rule, like `class` and `style`, are joined instead and not reported.
+
+Example of code the rule reports, with `button = { type = "button" }` in the
+[variables] section of nhtml.toml:
+
+    button ...button type='submit' 'Send';
+
+Leave out the value that isn't used, or turn the rule off if overriding
+spreads is intended:
+
+    [lint]
+    duplicate-attribute = "off"
+"#;
+
+const UNKNOWN_ATTRIBUTE: &str = r#"An element has an attribute that HTML doesn't define for it.
+
+This is usually a typo, or an attribute that belongs to another element.
+Global attributes are allowed everywhere, and so are `data-*`, `aria-*`,
+event handlers like `onclick`, and names with `-`, `:`, `.` or a leading `@`
+that frameworks use. Custom elements aren't checked.
+
+Example of code the rule reports:
+
+    div clas='card';
+
+Fix the name, or prefix custom attributes with `data-`:
+
+    div class='card' data-kind='card';
+"#;
+
+const ALT_TEXT: &str = r#"An image has no `alt` text.
+
+Screen readers read the `alt` text in place of the image, and browsers show
+it when the image doesn't load. This applies to `img`, `area` and
+`input type=image`.
+
+Example of code the rule reports:
+
+    img src='chart.png';
+
+Describe what the image shows:
+
+    img src='chart.png' alt='Sales doubled in 2023';
+
+Images that are only decoration get an empty `alt`, so they are skipped:
+
+    img src='divider.png' alt='';
+"#;
+
+const FORM_LABEL: &str = r#"A form control has no label.
+
+Without a label, screen readers can't tell what a field is for, and clicking
+the text next to it doesn't focus it. Inputs like `hidden`, `submit` and
+`button` don't need one.
+
+Example of code the rule reports:
+
+    'Email'
+    input type=email;
+
+Wrap the control in a `label`, point a `label for` at its `id`, or give it an
+`aria-label`:
+
+    label {
+        'Email'
+        input type=email;
+    }
+"#;
+
+const EXPLANATIONS: &[(&str, &str)] = &[
+    ("E0001", E0001),
+    ("E0002", E0002),
+    ("E0003", E0003),
+    ("E0004", E0004),
+    ("E0005", E0005),
+    ("E0006", E0006),
+    ("E0007", E0007),
+    ("E0008", E0008),
+    ("E0009", E0009),
+    ("E0010", E0010),
+    ("E0011", E0011),
+    ("E0012", E0012),
+    ("content-model", CONTENT_MODEL),
+    ("nested-interactive", NESTED_INTERACTIVE),
+    ("required-attributes", REQUIRED_ATTRIBUTES),
+    ("duplicate-id", DUPLICATE_ID),
+    ("duplicate-attribute", DUPLICATE_ATTRIBUTE),
+    ("unknown-attribute", UNKNOWN_ATTRIBUTE),
+    ("alt-text", ALT_TEXT),
+    ("form-label", FORM_LABEL),
+];
+
+// Looks up the explanation for an error code or lint rule, ignoring case
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, EXPLANATIONS};
+    use crate::{diagnostic::Diagnostic, transpile};
+
+    #[test]
+    fn test_explain_lookup() {
+        assert!(explain("E0003").unwrap().contains("<!DOCTYPE html>"));
+        assert_eq!(explain("e0003"), explain("E0003"));
+        assert_eq!(explain("E9999"), None);
+    }
+
+    #[test]
+    fn test_error_codes_are_explained() {
+        for src in ["p $;", "p 'a", "<a", "js{", "css{", "a b=;", "div }", "div {", "a b={c};", "a b b;"] {
+            let err = transpile(src).unwrap_err();
+            let code = Diagnostic::from_error(&err).code.unwrap_or_else(|| panic!("Missing code in '{err}'"));
+            assert!(EXPLANATIONS.iter().any(|(c, _)| *c == code), "No explanation for {code}");
+        }
+    }
+
+    #[test]
+    fn test_lint_rules_are_explained() {
+        for rule in crate::lint::RULES {
+            assert!(explain(rule.name).is_some(), "No explanation for {}", rule.name);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+pub mod position;
+pub mod scanner;
+pub mod parser;
+pub mod source;
+pub mod token;
+pub mod emitter;
+pub mod attributes;
+pub mod config;
+pub mod diagnostic;
+pub mod explain;
+pub mod lint;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod render;
+pub mod transform;
+pub mod variables;
+pub mod walk;
+#[cfg(test)]
+mod temp_dir;
+
+use std::{ffi::OsStr, path::Path};
+
+use anyhow::Result;
+
+use attributes::{Override, Source};
+use config::Options;
+use diagnostic::Diagnostic;
+use lint::LintConfig;
+use parser::ParseError;
+use scanner::Scanner;
+use transform::TransformError;
+use variables::VariableError;
+
+pub fn transpile(input: &str) -> Result<String> {
+    transpile_with(input, None, &Options::default())
+}
+
+// Transpiles source read from the file at `path`, which errors point at
+pub fn transpile_with(input: &str, path: Option<&Path>, opts: &Options) -> Result<String> {
+    let elems = parse(input, path, opts)?;
+    let res = emitter::emit_html(elems, &opts.emit, &opts.merge);
+
+    Ok(res)
+}
+
+// Like `transpile_with`, also returning a warning for every attribute that is
+// set more than once and takes the last value, at the level the
+// `duplicate-attribute` rule has
+pub fn transpile_with_warnings(input: &str, path: Option<&Path>, opts: &Options, lint: &LintConfig) -> Result<(String, Vec<Diagnostic>)> {
+    let elems = parse(input, path, opts)?;
+    let warnings = lint::overrides(&elems, input, path, lint, &opts.merge);
+
+    Ok((emitter::emit_html(elems, &opts.emit, &opts.merge), warnings))
+}
+
+// Runs every check transpiling does without emitting anything, followed by
+// the lint rules. Returns what the rules found.
+pub fn check(input: &str, path: Option<&Path>, opts: &Options, lint: &LintConfig) -> Result<Vec<Diagnostic>> {
+    let elems = parse(input, path, opts)?;
+
+    Ok(lint::lint(&elems, input, path, lint, &opts.merge))
+}
+
+// Parses the source, replaces the variables in it and runs the transforms
+pub fn parse(input: &str, path: Option<&Path>, opts: &Options) -> Result<Vec<parser::Element>> {
+    let mut scanner = Scanner::new(input, path);
+    let mut elems = parser::Parser::parse(&mut scanner)?;
+
+    if let Some(Override { name, first: Source::Explicit(first), last: Source::Explicit(pos) }) = attributes::duplicate(&elems, &opts.merge) {
+        let src = source::Source::new(input).with_path(path);
+        return Err(ParseError::DuplicateAttribute(name, pos.for_error(&src), first).into());
+    }
+
+    if let Err(e) = variables::interpolate(&mut elems, &opts.variables) {
+        let src = source::Source::new(input).with_path(path);
+        let err = match e {
+            VariableError::Unknown(name, pos) => ParseError::UnknownVariable(name, pos.for_error(&src)),
+            VariableError::WrongType { name, pos, expected } => ParseError::VariableType(name, expected, pos.for_error(&src)),
+        };
+        return Err(err.into());
+    }
+
+    if let Err(err) = opts.transforms.run(&mut elems) {
+        return Err(match err.downcast::<TransformError>() {
+            Ok(TransformError { transform, message, pos: Some(pos), related }) => {
+                let src = source::Source::new(input).with_path(path);
+                ParseError::Transform(transform, message, pos.for_error(&src), related).into()
+            },
+            Ok(err) => err.into(),
+            Err(err) => err,
+        });
+    }
+
+    Ok(elems)
+}
+
+pub fn check_extension(path: &Path, ext: &str) -> bool {
+    path.extension().and_then(OsStr::to_str) == Some(ext)
+}
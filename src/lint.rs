@@ -0,0 +1,628 @@
+use std::{collections::{BTreeMap, HashMap, HashSet}, path::Path};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::{attributes::{self, Merged, MergeRules, Override, Source}, diagnostic::{Diagnostic, Severity}, parser::{Attrib, Element, Tag, Value}, position::Position, walk};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Warn,
+    Error,
+}
+
+pub struct Rule {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        name: "content-model",
+        description: "elements only contain the children HTML allows, like `li` in `ul`",
+    },
+    Rule {
+        name: "nested-interactive",
+        description: "links and buttons are not nested in each other",
+    },
+    Rule {
+        name: "required-attributes",
+        description: "elements have the attributes they don't work without, like `src` on `img`",
+    },
+    Rule {
+        name: "duplicate-id",
+        description: "every `id` is unique in the page",
+    },
+    Rule {
+        name: "duplicate-attribute",
+        description: "attributes other than `class` and `style` are set once, including through spreads",
+    },
+    Rule {
+        name: "unknown-attribute",
+        description: "known elements only use attributes they support",
+    },
+    Rule {
+        name: "alt-text",
+        description: "images have an `alt` text",
+    },
+    Rule {
+        name: "form-label",
+        description: "form controls have a label",
+    },
+];
+
+// The level every rule is reported at. Rules are warnings by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintConfig {
+    levels: BTreeMap<String, Level>,
+}
+
+impl LintConfig {
+    pub fn new(levels: BTreeMap<String, Level>) -> Result<Self> {
+        for name in levels.keys() {
+            if !RULES.iter().any(|rule| rule.name == name) {
+                bail!("Unknown lint rule '{name}'");
+            }
+        }
+
+        Ok(Self { levels })
+    }
+
+    pub fn level(&self, rule: &str) -> Level {
+        self.levels.get(rule).copied().unwrap_or(Level::Warn)
+    }
+}
+
+// Attributes every HTML element accepts
+const GLOBAL_ATTRIBUTES: &[&str] = &[
+    "accesskey", "autocapitalize", "autofocus", "class", "contenteditable", "dir",
+    "draggable", "enterkeyhint", "hidden", "id", "inert", "inputmode", "is", "itemid",
+    "itemprop", "itemref", "itemscope", "itemtype", "lang", "nonce", "part", "popover",
+    "role", "slot", "spellcheck", "style", "tabindex", "title", "translate", "xmlns",
+];
+
+// Event handler attributes, which every element accepts
+const EVENT_HANDLERS: &[&str] = &[
+    "onabort", "onafterprint", "onanimationcancel", "onanimationend",
+    "onanimationiteration", "onanimationstart", "onauxclick", "onbeforeinput",
+    "onbeforematch", "onbeforeprint", "onbeforetoggle", "onbeforeunload", "onblur",
+    "oncancel", "oncanplay", "oncanplaythrough", "onchange", "onclick", "onclose",
+    "oncontextlost", "oncontextmenu", "oncontextrestored", "oncopy", "oncuechange", "oncut",
+    "ondblclick", "ondrag", "ondragend", "ondragenter", "ondragleave", "ondragover",
+    "ondragstart", "ondrop", "ondurationchange", "onemptied", "onended", "onerror",
+    "onfocus", "onfocusin", "onfocusout", "onformdata", "onhashchange", "oninput",
+    "oninvalid", "onkeydown", "onkeypress", "onkeyup", "onlanguagechange", "onload",
+    "onloadeddata", "onloadedmetadata", "onloadstart", "onmessage", "onmessageerror",
+    "onmousedown", "onmouseenter", "onmouseleave", "onmousemove", "onmouseout",
+    "onmouseover", "onmouseup", "onoffline", "ononline", "onpagehide", "onpagereveal",
+    "onpageshow", "onpageswap", "onpaste", "onpause", "onplay", "onplaying",
+    "onpointercancel", "onpointerdown", "onpointerenter", "onpointerleave", "onpointermove",
+    "onpointerout", "onpointerover", "onpointerup", "onpopstate", "onprogress",
+    "onratechange", "onrejectionhandled", "onreset", "onresize", "onscroll", "onscrollend",
+    "onsecuritypolicyviolation", "onseeked", "onseeking", "onselect", "onselectionchange",
+    "onselectstart", "onslotchange", "onstalled", "onstorage", "onsubmit", "onsuspend",
+    "ontimeupdate", "ontoggle", "ontouchcancel", "ontouchend", "ontouchmove",
+    "ontouchstart", "ontransitioncancel", "ontransitionend", "ontransitionrun",
+    "ontransitionstart", "onunhandledrejection", "onunload", "onvolumechange", "onwaiting",
+    "onwheel",
+];
+
+// Attributes of the HTML elements the linter knows. Elements that aren't in
+// this list, like custom elements and SVG, are not checked.
+const ELEMENT_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "target", "download", "ping", "rel", "hreflang", "type", "referrerpolicy"]),
+    ("abbr", &[]), ("address", &[]), ("article", &[]), ("aside", &[]), ("b", &[]),
+    ("bdi", &[]), ("bdo", &[]), ("body", &[]), ("br", &[]), ("caption", &[]), ("cite", &[]),
+    ("code", &[]), ("dd", &[]), ("dfn", &[]), ("div", &[]), ("dl", &[]), ("dt", &[]),
+    ("em", &[]), ("figcaption", &[]), ("figure", &[]), ("footer", &[]), ("h1", &[]),
+    ("h2", &[]), ("h3", &[]), ("h4", &[]), ("h5", &[]), ("h6", &[]), ("head", &[]),
+    ("header", &[]), ("hgroup", &[]), ("hr", &[]), ("i", &[]), ("kbd", &[]), ("legend", &[]),
+    ("main", &[]), ("mark", &[]), ("menu", &[]), ("nav", &[]), ("noscript", &[]), ("p", &[]),
+    ("picture", &[]), ("pre", &[]), ("rp", &[]), ("rt", &[]), ("ruby", &[]), ("s", &[]),
+    ("samp", &[]), ("search", &[]), ("section", &[]), ("small", &[]), ("span", &[]),
+    ("strong", &[]), ("sub", &[]), ("summary", &[]), ("sup", &[]), ("table", &[]),
+    ("tbody", &[]), ("tfoot", &[]), ("thead", &[]), ("title", &[]), ("tr", &[]), ("u", &[]),
+    ("ul", &[]), ("var", &[]), ("wbr", &[]),
+    ("area", &["alt", "coords", "shape", "href", "target", "download", "ping", "rel", "referrerpolicy"]),
+    ("audio", &["src", "crossorigin", "preload", "autoplay", "loop", "muted", "controls"]),
+    ("base", &["href", "target"]),
+    ("blockquote", &["cite"]),
+    ("button", &[
+        "disabled", "form", "formaction", "formenctype", "formmethod", "formnovalidate",
+        "formtarget", "name", "popovertarget", "popovertargetaction", "type", "value",
+    ]),
+    ("canvas", &["width", "height"]),
+    ("col", &["span"]),
+    ("colgroup", &["span"]),
+    ("data", &["value"]),
+    ("del", &["cite", "datetime"]),
+    ("details", &["open", "name"]),
+    ("dialog", &["open"]),
+    ("embed", &["src", "type", "width", "height"]),
+    ("fieldset", &["disabled", "form", "name"]),
+    ("form", &["accept-charset", "action", "autocomplete", "enctype", "method", "name", "novalidate", "rel", "target"]),
+    ("html", &["manifest", "version"]),
+    ("iframe", &["src", "srcdoc", "name", "sandbox", "allow", "allowfullscreen", "width", "height", "referrerpolicy", "loading"]),
+    ("img", &[
+        "alt", "src", "srcset", "sizes", "crossorigin", "usemap", "ismap", "width", "height",
+        "referrerpolicy", "decoding", "loading", "fetchpriority",
+    ]),
+    ("input", &[
+        "accept", "alt", "autocomplete", "checked", "dirname", "disabled", "form", "formaction",
+        "formenctype", "formmethod", "formnovalidate", "formtarget", "height", "list", "max",
+        "maxlength", "min", "minlength", "multiple", "name", "pattern", "placeholder",
+        "popovertarget", "popovertargetaction", "readonly", "required", "size", "src", "step",
+        "type", "value", "width",
+    ]),
+    ("ins", &["cite", "datetime"]),
+    ("label", &["for"]),
+    ("li", &["value"]),
+    ("link", &[
+        "as", "blocking", "crossorigin", "disabled", "fetchpriority", "href", "hreflang",
+        "imagesizes", "imagesrcset", "integrity", "media", "referrerpolicy", "rel", "sizes", "type",
+    ]),
+    ("map", &["name"]),
+    ("meta", &["charset", "content", "http-equiv", "media", "name", "property"]),
+    ("meter", &["value", "min", "max", "low", "high", "optimum"]),
+    ("object", &["data", "type", "name", "form", "width", "height"]),
+    ("ol", &["reversed", "start", "type"]),
+    ("optgroup", &["disabled", "label"]),
+    ("option", &["disabled", "label", "selected", "value"]),
+    ("output", &["for", "form", "name"]),
+    ("progress", &["value", "max"]),
+    ("q", &["cite"]),
+    ("script", &["async", "blocking", "crossorigin", "defer", "fetchpriority", "integrity", "nomodule", "referrerpolicy", "src", "type"]),
+    ("select", &["autocomplete", "disabled", "form", "multiple", "name", "required", "size"]),
+    ("slot", &["name"]),
+    ("source", &["type", "media", "src", "srcset", "sizes", "width", "height"]),
+    ("style", &["media", "blocking"]),
+    ("td", &["colspan", "rowspan", "headers"]),
+    ("template", &["shadowrootmode", "shadowrootdelegatesfocus", "shadowrootclonable"]),
+    ("textarea", &[
+        "autocomplete", "cols", "dirname", "disabled", "form", "maxlength", "minlength", "name",
+        "placeholder", "readonly", "required", "rows", "wrap",
+    ]),
+    ("th", &["colspan", "rowspan", "headers", "scope", "abbr"]),
+    ("time", &["datetime"]),
+    ("track", &["default", "kind", "label", "src", "srclang"]),
+    ("video", &[
+        "src", "crossorigin", "poster", "preload", "autoplay", "playsinline", "loop", "muted",
+        "controls", "width", "height",
+    ]),
+];
+
+// Elements that may only contain certain children. Scripts and templates
+// are allowed everywhere.
+const ALLOWED_CHILDREN: &[(&str, &[&str])] = &[
+    ("ul", &["li"]),
+    ("ol", &["li"]),
+    ("menu", &["li"]),
+    ("dl", &["dt", "dd", "div"]),
+    ("table", &["caption", "colgroup", "thead", "tbody", "tfoot", "tr"]),
+    ("thead", &["tr"]),
+    ("tbody", &["tr"]),
+    ("tfoot", &["tr"]),
+    ("tr", &["td", "th"]),
+    ("colgroup", &["col"]),
+    ("select", &["option", "optgroup", "hr"]),
+    ("optgroup", &["option"]),
+];
+
+// Elements that end a paragraph, so they can't be inside a `p`
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "details", "dialog", "div", "dl", "fieldset",
+    "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header",
+    "hgroup", "hr", "main", "menu", "nav", "ol", "p", "pre", "search", "section", "table", "ul",
+];
+
+const INTERACTIVE: &[&str] = &["a", "button"];
+
+// Elements that need one of several sets of attributes to work
+const REQUIRED_ATTRIBUTES: &[(&str, &[&[&str]])] = &[
+    ("img", &[&["src"], &["srcset"]]),
+    ("link", &[&["rel", "href"]]),
+    ("iframe", &[&["src"], &["srcdoc"]]),
+    ("source", &[&["src"], &["srcset"]]),
+    ("track", &[&["src"]]),
+    ("area", &[&["href"], &["nohref"]]),
+];
+
+// Input types that don't need a label
+const UNLABELED_INPUTS: &[&str] = &["hidden", "submit", "reset", "button", "image"];
+
+// Walks a page and reports every rule that is broken
+pub fn lint(elements: &[Element], source: &str, path: Option<&Path>, config: &LintConfig, merge: &MergeRules) -> Vec<Diagnostic> {
+    let mut linter = Linter::new(config, merge);
+
+    linter.collect_labels(elements);
+    walk::walk(elements, &mut |element, ancestors| linter.element(element, ancestors));
+
+    linter.finish(source, path)
+}
+
+// Only the `duplicate-attribute` rule. Pages that are built use the last
+// value without a word, so builds report these too.
+pub fn overrides(elements: &[Element], source: &str, path: Option<&Path>, config: &LintConfig, merge: &MergeRules) -> Vec<Diagnostic> {
+    let mut linter = Linter::new(config, merge);
+
+    walk::walk(elements, &mut |element, _| {
+        if let Element::Tag(tag) = element {
+            let merged = attributes::merge(&tag.attribs, linter.merge);
+            linter.duplicate_attributes(tag, &merged);
+        }
+    });
+
+    linter.finish(source, path)
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    merge: &'a MergeRules,
+    // Ids referenced by the `for` attribute of a label
+    labelled: HashSet<String>,
+    // The first tag with every id
+    ids: HashMap<String, &'a Tag>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(config: &'a LintConfig, merge: &'a MergeRules) -> Self {
+        Self {
+            config,
+            merge,
+            labelled: HashSet::new(),
+            ids: HashMap::new(),
+            diagnostics: vec![],
+        }
+    }
+
+    fn finish(self, source: &str, path: Option<&Path>) -> Vec<Diagnostic> {
+        self.diagnostics.into_iter()
+            .map(|diag| diag.with_path(path).with_source(source))
+            .collect()
+    }
+
+    fn element(&mut self, element: &'a Element, ancestors: &[&'a Tag]) {
+        let parent = ancestors.last().copied();
+
+        match element {
+            Element::Tag(tag) => self.tag(tag, parent, ancestors),
+            Element::Text(text) => {
+                let Some(parent) = parent else {
+                    return;
+                };
+
+                let parent_ty = parent.ty.to_ascii_lowercase();
+                if allowed_children(&parent_ty).is_some() && !text.trim().is_empty() {
+                    let parent_pos = parent.ty.pos;
+                    self.report("content-model", element.pos(), format!("Text is not allowed directly inside `{}`", parent.ty), |diag| diag
+                        .with_secondary_label(parent_pos, "parent element")
+                        .with_note(&children_note(&parent_ty)));
+                }
+            },
+            Element::Html(_) |
+            Element::Js(_) |
+            Element::Css(_) => {},
+        }
+    }
+
+    fn tag(&mut self, tag: &'a Tag, parent: Option<&'a Tag>, ancestors: &[&'a Tag]) {
+        let ty = tag.ty.to_ascii_lowercase();
+
+        if let Some(parent) = parent {
+            self.content_model(tag, &ty, parent);
+        }
+
+        if INTERACTIVE.contains(&ty.as_str()) {
+            if let Some(outer) = ancestors.iter().rev().find(|a| INTERACTIVE.contains(&a.ty.to_ascii_lowercase().as_str())) {
+                let outer_pos = outer.ty.pos;
+                self.report("nested-interactive", tag.ty.pos, format!("`{}` is nested inside `{}`", tag.ty, outer.ty), |diag| diag
+                    .with_secondary_label(outer_pos, "outer element")
+                    .with_note("browsers split nested links and buttons apart, and it is unclear which one is activated"));
+            }
+        }
+
+        // Attributes are looked up the way they are emitted, after spreads
+        // are merged
+        let merged = attributes::merge(&tag.attribs, self.merge);
+
+        self.required_attributes(tag, &ty, &merged);
+        self.duplicate_attributes(tag, &merged);
+        self.duplicate_id(tag, &merged);
+        self.unknown_attributes(tag, &ty);
+        self.accessibility(tag, &ty, &merged, ancestors);
+    }
+
+    fn content_model(&mut self, tag: &Tag, ty: &str, parent: &Tag) {
+        let parent_ty = parent.ty.to_ascii_lowercase();
+
+        if matches!(ty, "script" | "template") {
+            return;
+        }
+
+        if let Some(allowed) = allowed_children(&parent_ty) {
+            if !allowed.contains(&ty) {
+                let parent_pos = parent.ty.pos;
+                self.report("content-model", tag.ty.pos, format!("`{}` is not allowed inside `{}`", tag.ty, parent.ty), |diag| diag
+                    .with_secondary_label(parent_pos, "parent element")
+                    .with_note(&children_note(&parent_ty)));
+            }
+            return;
+        }
+
+        if parent_ty == "p" && BLOCK_ELEMENTS.contains(&ty) {
+            let parent_pos = parent.ty.pos;
+            self.report("content-model", tag.ty.pos, format!("`{}` is not allowed inside `p`", tag.ty), |diag| diag
+                .with_secondary_label(parent_pos, "paragraph")
+                .with_note("browsers close the paragraph before block elements like this one"));
+        }
+
+        if ty == "li" && !matches!(parent_ty.as_str(), "ul" | "ol" | "menu") {
+            self.report("content-model", tag.ty.pos, "`li` is only allowed inside `ul`, `ol` or `menu`".to_owned(), |diag| diag);
+        }
+    }
+
+    fn required_attributes(&mut self, tag: &Tag, ty: &str, merged: &Merged) {
+        let Some((_, sets)) = REQUIRED_ATTRIBUTES.iter().find(|(name, _)| *name == ty) else {
+            return;
+        };
+
+        let satisfied = sets.iter().any(|set| set.iter().all(|attr| has_attrib(merged, attr)));
+        if satisfied {
+            return;
+        }
+
+        let missing: Vec<String> = sets[0].iter()
+            .filter(|attr| !has_attrib(merged, attr))
+            .map(|attr| format!("`{attr}`"))
+            .collect();
+
+        let alternatives: Vec<String> = sets.iter()
+            .map(|set| set.iter().map(|attr| format!("`{attr}`")).collect::<Vec<_>>().join(" and "))
+            .collect();
+
+        self.report("required-attributes", tag.ty.pos, format!("`{}` is missing {}", tag.ty, missing.join(" and ")), |diag| {
+            if alternatives.len() > 1 {
+                diag.with_note(&format!("`{}` needs one of {}", tag.ty, alternatives.join(", ")))
+            } else {
+                diag
+            }
+        });
+    }
+
+    fn duplicate_attributes(&mut self, tag: &Tag, merged: &Merged) {
+        for Override { name, first, last } in &merged.overrides {
+            let describe = |source: &Source| match source {
+                Source::Explicit(_) => format!("`{name}`"),
+                Source::Spread(var, _) => format!("`...{var}`"),
+            };
+            let note = format!("the value from {} replaces the one from {}", describe(last), describe(first));
+
+            self.report("duplicate-attribute", tag.ty.pos, format!("`{name}` is set more than once"), |mut diag| {
+                for (source, label) in [(first, "first set here"), (last, "set again here")] {
+                    let (Source::Explicit(pos) | Source::Spread(_, pos)) = source;
+                    diag = diag.with_secondary_label(*pos, label);
+                }
+                diag.with_note(&note)
+            });
+        }
+    }
+
+    fn duplicate_id(&mut self, tag: &'a Tag, merged: &Merged) {
+        let Some(id) = attrib_value(merged, "id") else {
+            return;
+        };
+
+        match self.ids.get(&id) {
+            Some(first) => {
+                let first_pos = first.ty.pos;
+                self.report("duplicate-id", tag.ty.pos, format!("The id `{id}` is used more than once"), |diag| diag.with_secondary_label(first_pos, "first used here"));
+            },
+            None => {
+                self.ids.insert(id, tag);
+            },
+        }
+    }
+
+    fn unknown_attributes(&mut self, tag: &Tag, ty: &str) {
+        let Some((_, attributes)) = ELEMENT_ATTRIBUTES.iter().find(|(name, _)| *name == ty) else {
+            return;
+        };
+
+        // Attributes from a spread are reported at the spread
+        let names: Vec<(&String, Position)> = tag.attribs.iter()
+            .flat_map(|attrib| match attrib {
+                Attrib::Named { name, .. } => vec![(&name.node, name.pos)],
+                Attrib::Spread { name: spread, attribs } => attribs.iter().map(|(name, _)| (name, spread.pos)).collect(),
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        for (name, pos) in names {
+            let lower = name.to_ascii_lowercase();
+            if !seen.insert(lower.clone()) {
+                continue;
+            }
+
+            // Data and ARIA attributes, along with anything that looks like
+            // it belongs to a framework, are always allowed
+            let custom = lower.contains(['-', ':', '.']) || lower.starts_with('@');
+
+            let known = [GLOBAL_ATTRIBUTES, EVENT_HANDLERS, attributes].iter().any(|names| names.contains(&lower.as_str()));
+            if custom || known {
+                continue;
+            }
+
+            self.report("unknown-attribute", pos, format!("`{}` has no attribute `{name}`", tag.ty), |diag| diag.with_note("prefix custom attributes with `data-`"));
+        }
+    }
+
+    fn accessibility(&mut self, tag: &Tag, ty: &str, merged: &Merged, ancestors: &[&Tag]) {
+        let needs_alt = match ty {
+            "img" | "area" => true,
+            "input" => attrib_value(merged, "type").is_some_and(|t| t.eq_ignore_ascii_case("image")),
+            _ => false,
+        };
+
+        if needs_alt && !has_attrib(merged, "alt") {
+            self.report("alt-text", tag.ty.pos, format!("`{}` has no `alt` text", tag.ty), |diag| diag.with_note("describe the image for screen readers, or use `alt=''` if it is decorative"));
+        }
+
+        let is_control = match ty {
+            "select" | "textarea" => true,
+            "input" => !attrib_value(merged, "type")
+                .is_some_and(|t| UNLABELED_INPUTS.iter().any(|u| t.eq_ignore_ascii_case(u))),
+            _ => false,
+        };
+
+        if !is_control {
+            return;
+        }
+
+        let labelled = ["aria-label", "aria-labelledby", "title"].iter().any(|attr| has_attrib(merged, attr)) ||
+            attrib_value(merged, "id").is_some_and(|id| self.labelled.contains(&id)) ||
+            ancestors.iter().any(|a| a.ty.eq_ignore_ascii_case("label"));
+
+        if !labelled {
+            self.report("form-label", tag.ty.pos, format!("`{}` has no label", tag.ty), |diag| diag.with_note("wrap it in a `label`, point a `label for` at its `id`, or give it an `aria-label`"));
+        }
+    }
+
+    fn collect_labels(&mut self, elements: &[Element]) {
+        walk::walk(elements, &mut |element, _| {
+            if let Element::Tag(tag) = element {
+                if tag.ty.eq_ignore_ascii_case("label") {
+                    let merged = attributes::merge(&tag.attribs, self.merge);
+                    if let Some(id) = attrib_value(&merged, "for") {
+                        self.labelled.insert(id);
+                    }
+                }
+            }
+        });
+    }
+
+    // Adds a diagnostic for the rule at the position unless the rule is
+    // turned off, letting the caller add notes and labels to it
+    fn report(&mut self, rule: &'static str, pos: Position, message: String, extend: impl FnOnce(Diagnostic) -> Diagnostic) {
+        let severity = match self.config.level(rule) {
+            Level::Off => return,
+            Level::Warn => Severity::Warning,
+            Level::Error => Severity::Error,
+        };
+
+        let diag = Diagnostic::new(severity, Some(rule), message).with_label(pos, None);
+        self.diagnostics.push(extend(diag));
+    }
+}
+
+// Boolean attributes set to `false` are left out when they are emitted, so
+// they don't count
+fn has_attrib(merged: &Merged, name: &str) -> bool {
+    merged.attribs.iter()
+        .any(|(attr, value)| attr.eq_ignore_ascii_case(name) && (*value != Some(Value::Bool(false)) || !attributes::is_boolean(attr)))
+}
+
+fn attrib_value(merged: &Merged, name: &str) -> Option<String> {
+    merged.attribs.iter()
+        .find(|(attr, _)| attr.eq_ignore_ascii_case(name))
+        .and_then(|(_, value)| Some(value.as_ref()?.as_str()?.to_owned()))
+}
+
+fn allowed_children(ty: &str) -> Option<&'static [&'static str]> {
+    ALLOWED_CHILDREN.iter()
+        .find(|(name, _)| *name == ty)
+        .map(|(_, children)| *children)
+}
+
+fn children_note(ty: &str) -> String {
+    let children: Vec<String> = allowed_children(ty).unwrap_or_default()
+        .iter()
+        .map(|child| format!("`{child}`"))
+        .collect();
+
+    format!("`{ty}` may only contain {}", children.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{lint, Level, LintConfig};
+    use crate::{attributes::MergeRules, diagnostic::Severity, parser::Parser, scanner::Scanner};
+
+    fn lint_str(src: &str, config: &LintConfig) -> Vec<(&'static str, String)> {
+        let mut scanner = Scanner::new(src, None);
+        let elements = Parser::parse(&mut scanner).unwrap();
+
+        lint(&elements, src, None, config, &MergeRules::default()).into_iter()
+            .map(|diag| (diag.code.unwrap(), diag.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let src = r#"
+            ul { p 'x'; li 'y'; }
+            p { div; }
+            a href='/' { a href='/b' 'nested'; }
+            img src='a.png';
+            img alt='';
+            div id='main'; span id="main";
+            div clas='x' data-x='1' @click='go' x-on:keyup.enter='go' onclick='go()' onclik='go()';
+            label for='email' 'Email'; input id='email';
+            input name='q';
+            label { 'Name' input; }
+            my-element foo='bar';
+        "#;
+
+        let found = lint_str(src, &LintConfig::default());
+        let expect = [
+            ("content-model", "`p` is not allowed inside `ul`"),
+            ("content-model", "`div` is not allowed inside `p`"),
+            ("nested-interactive", "`a` is nested inside `a`"),
+            ("alt-text", "`img` has no `alt` text"),
+            ("required-attributes", "`img` is missing `src`"),
+            ("duplicate-id", "The id `main` is used more than once"),
+            ("unknown-attribute", "`div` has no attribute `clas`"),
+            ("unknown-attribute", "`div` has no attribute `onclik`"),
+            ("form-label", "`input` has no label"),
+        ];
+
+        assert_eq!(found, expect.map(|(rule, msg)| (rule, msg.to_owned())));
+    }
+
+    #[test]
+    fn test_positions() {
+        let src = "div clas='x';\nul { 'text' }";
+        let mut scanner = Scanner::new(src, None);
+        let elements = Parser::parse(&mut scanner).unwrap();
+        let diags = lint(&elements, src, None, &LintConfig::default(), &MergeRules::default());
+
+        let starts: Vec<_> = diags.iter()
+            .map(|diag| diag.labels.iter().map(|label| (label.pos.start_ln, label.pos.start_cn, label.pos.len)).collect::<Vec<_>>())
+            .collect();
+
+        // The attribute name, and the text with its parent
+        assert_eq!(starts, vec![vec![(1, 5, 4)], vec![(2, 6, 6), (2, 1, 2)]]);
+    }
+
+    #[test]
+    fn test_rule_levels() {
+        let config = LintConfig::new(BTreeMap::from([
+            ("alt-text".to_owned(), Level::Off),
+            ("required-attributes".to_owned(), Level::Error),
+        ])).unwrap();
+
+        let mut scanner = Scanner::new("img;", None);
+        let elements = crate::parser::Parser::parse(&mut scanner).unwrap();
+        let diags = lint(&elements, "img;", None, &config, &MergeRules::default());
+
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code, Some("required-attributes"));
+        assert_eq!(diags[0].severity, Severity::Error);
+
+        assert!(LintConfig::new(BTreeMap::from([("alt".to_owned(), Level::Off)])).is_err());
+    }
+}
@@ -1,200 +1,612 @@
-mod position;
-mod scanner;
-mod parser;
-mod source;
-mod token;
-mod emitter;
-
-use std::{ffi::OsStr, path::{Path, PathBuf}, time::Duration};
-
-use clap::{Parser, Subcommand};
-use scanner::Scanner;
+mod assets;
+mod cache;
+mod serve;
+mod watch;
+#[cfg(test)]
+mod temp_dir;
+
+use std::{
+    ffi::OsStr,
+    io::{IsTerminal, Read, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use cache::BuildCache;
+use nhtml::{check, check_extension, scanner::Scanner, config::{self, Config}, diagnostic::{self, Diagnostic, Severity}, explain, lint, render, transpile_with, transpile_with_warnings};
+use clap::{Parser, Subcommand, ValueEnum};
 use anyhow::{Result, Context};
 
-pub fn transpile(input: &str) -> Result<String> {
-    let mut scanner = Scanner::new(input);
-    let elems = parser::Parser::parse(&mut scanner)?;
-    let res = emitter::emit_html(elems);
-
-    Ok(res)
-}
-
-
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// How errors are reported
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    /// Human readable messages with source snippets
+    Human,
+    /// One JSON object per diagnostic, one per line
+    Json,
+    /// A SARIF 2.1.0 log
+    Sarif,
+}
+
+// Path standing for stdin or stdout on the command line
+const STDIO: &str = "-";
+
+// Attached as context to errors so the failing file can be reported
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to transpile '{}'", .0.display())]
+struct FileError(PathBuf);
+
 #[derive(Subcommand)]
 enum Commands {
     Convert {
+        /// File or directory to convert, `-` reads from stdin
         path: PathBuf,
-        output: PathBuf,
+        /// Where to write the result, stdout if omitted or `-`
+        output: Option<PathBuf>,
+        #[arg(short, long = "output", value_name = "OUTPUT", conflicts_with = "output")]
+        out: Option<PathBuf>,
     },
     Watch {
         path: PathBuf,
         output: PathBuf,
-    }
-}
-
-fn check_extension(path: &Path, ext: &str) -> bool {
-    return path.extension().and_then(OsStr::to_str) == Some(ext);
+    },
+    /// Build the project configured in the nearest nhtml.toml
+    Build,
+    /// Check that pages are valid without writing any output
+    Check {
+        /// Files or directories to check, the project source directory by default
+        paths: Vec<PathBuf>,
+    },
+    /// Check pages like `check`, also failing on lint warnings
+    Lint {
+        /// Files or directories to lint, the project source directory by default
+        paths: Vec<PathBuf>,
+    },
+    /// Serve a directory on localhost, reloading the browser on changes
+    Serve {
+        path: PathBuf,
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    /// Print the syntax tree of a page as JSON, before variables are replaced
+    Ast {
+        /// File to parse, `-` reads from stdin
+        path: PathBuf,
+    },
+    /// Print the tokens of a page as JSON
+    Tokens {
+        /// File to scan, `-` reads from stdin
+        path: PathBuf,
+    },
+    /// Print a detailed description of an error code or lint rule
+    Explain {
+        code: String,
+    },
 }
 
-fn transpile_file(path: &Path, output: &Path) -> Result<()> {
+// Returns the warnings for attributes that were set more than once
+fn transpile_file(path: &Path, output: &Path, config: &Config) -> Result<Vec<Diagnostic>> {
     let input = std::fs::read_to_string(path)
         .context("Failed to read input file")?;
-    let res = transpile(&input)?;
+    let (res, warnings) = transpile_with_warnings(&input, Some(path), &config.options_for(path), &config.lint)
+        .with_context(|| FileError(path.to_path_buf()))?;
     let parent = output.parent()
         .context(format!("Failed to get parent directory of file {output:?}"))?;
     std::fs::create_dir_all(parent)?;
     std::fs::write(output, res).context("Failed to write transpiled code to file")?;
-    Ok(())
+    Ok(warnings)
+}
+
+// Transpiles a page unless the cache shows that none of the files it was
+// built from changed. Returns the warnings for the page if it was built.
+fn build_page(path: &Path, output: &Path, config: &Config, cache: &BuildCache) -> Result<Option<Vec<Diagnostic>>> {
+    if cache.is_fresh(path, output) {
+        return Ok(None);
+    }
+
+    let warnings = transpile_file(path, output, config)?;
+
+    // Pages are built from their own source, the project configuration and
+    // its plugins. Any other file read while transpiling a page belongs in
+    // this list.
+    let mut deps = vec![path.to_path_buf()];
+    deps.extend(config.file.clone());
+    deps.extend(config.plugins.iter().cloned());
+
+    cache.record(path, output, &deps)?;
+    Ok(Some(warnings))
+}
+
+// The outcome of building a set of pages and copying assets
+#[derive(Debug, Default)]
+struct Summary {
+    built: usize,
+    copied: usize,
+    fresh: usize,
+    errors: Vec<anyhow::Error>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Summary {
+    fn add(&mut self, res: Result<Option<Vec<Diagnostic>>>) {
+        match res {
+            Ok(Some(warnings)) => {
+                self.built += 1;
+                self.warnings.extend(warnings);
+            },
+            Ok(None) => self.fresh += 1,
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    // Warnings followed by errors, reported together so SARIF output is a
+    // single log
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.warnings.iter().cloned()
+            .chain(self.errors.iter().map(to_diagnostic))
+            .collect()
+    }
+
+    fn add_asset(&mut self, res: Result<bool>) {
+        match res {
+            Ok(true) => self.copied += 1,
+            Ok(false) => self.fresh += 1,
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    fn line(&self, elapsed: Duration) -> String {
+        format!(
+            "Finished: {} built, {} copied, {} up to date, {} failed in {:.2?}",
+            self.built,
+            self.copied,
+            self.fresh,
+            self.errors.len(),
+            elapsed,
+        )
+    }
 }
 
-fn transpile_dir(input: &Path, dir: &Path, output: &Path) -> Result<()> {
+// Source files in a directory, each with where its output goes
+#[derive(Debug, Default)]
+struct Files {
+    pages: Vec<(PathBuf, PathBuf)>,
+    assets: Vec<(PathBuf, PathBuf)>,
+}
+
+// Collects every page and asset in the directory that isn't ignored. The
+// output directory is skipped when it is inside the input.
+fn find_files(input: &Path, dir: &Path, output: &Path, config: &Config, files: &mut Files) -> Result<()> {
     let cwd = std::env::current_dir()?;
     let base_path: PathBuf = if input.is_absolute() { input.to_path_buf() } else { cwd.join(input) };
+    let output_dir = output.canonicalize().ok();
 
     for dir_entry in dir.read_dir()? {
         let path_buf = dir_entry?.path();
         let path = Path::new(&path_buf);
 
-        if !path.is_dir() && !check_extension(path, "nhtml") {
+        if path.is_dir() {
+            if !config.is_ignored(path) && path.canonicalize().ok() != output_dir {
+                find_files(input, path, output, config, files)?;
+            }
             continue;
         }
 
-        if path.is_dir() {
-            transpile_dir(input, path, output)?;
-        } else {
-            let relative_out = if path.is_absolute() { path.strip_prefix(&base_path)? } else { path.strip_prefix(input)? };
-            let out = output.join(relative_out)
-                .with_extension("html");
-
-            transpile_file(
-                path,
-                &out
-            )?;
+        let relative_out = if path.is_absolute() { path.strip_prefix(&base_path)? } else { path.strip_prefix(input)? };
+        let out = output.join(relative_out);
+
+        if check_extension(path, "nhtml") && !config.is_ignored(path) {
+            files.pages.push((path.to_path_buf(), out.with_extension("html")));
+        } else if config.is_asset(path) && path.file_name() != Some(OsStr::new(cache::CACHE_FILE)) {
+            files.assets.push((path.to_path_buf(), out));
         }
     }
 
     Ok(())
 }
 
-fn transpile_from_to(path: &Path, output: &Path) -> Result<()> {
+// Runs the job for every item on a pool of threads. Every item is attempted
+// even if others fail, and results are returned in the order of the items.
+fn run_parallel<T, R, F>(items: &[T], job: F) -> Vec<Result<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let workers = std::thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(items.len())
+        .max(1);
+
+    let mut results: Vec<(usize, Result<R>)> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| s.spawn(|| {
+                let mut results = vec![];
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    results.push((i, job(item)));
+                }
+                results
+            }))
+            .collect();
+
+        handles.into_iter()
+            .flat_map(|handle| handle.join().expect("build thread panicked"))
+            .collect()
+    });
+
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, res)| res).collect()
+}
+
+fn transpile_dir(input: &Path, dir: &Path, output: &Path, config: &Config, cache: &BuildCache) -> Result<Summary> {
+    let mut files = Files::default();
+    find_files(input, dir, output, config, &mut files)?;
+
+    // Sorted so errors and output are the same on every run
+    files.pages.sort();
+    files.assets.sort();
+
+    let mut summary = Summary::default();
+
+    run_parallel(&files.pages, |(path, out)| build_page(path, out, config, cache))
+        .into_iter()
+        .for_each(|res| summary.add(res));
+
+    run_parallel(&files.assets, |(path, out)| assets::copy_asset(path, out, config.hard_link))
+        .into_iter()
+        .for_each(|res| summary.add_asset(res));
+
+    Ok(summary)
+}
+
+fn transpile_from_to(path: &Path, output: &Path, config: &Config, cache: &BuildCache) -> Result<Summary> {
     if path.is_dir() {
-        return transpile_dir(path, path, output);
+        return transpile_dir(path, path, output, config, cache);
     }
 
-    if output.is_dir() {
+    let out_file = if output.is_dir() {
         let file_name = path.file_name()
             .context("Failed to get file name")?;
-        let out_file = output.join(file_name)
-            .with_extension("html");
-        return transpile_file(path, &out_file);
+        output.join(file_name).with_extension("html")
+    } else {
+        output.to_path_buf()
+    };
+
+    let mut summary = Summary::default();
+    summary.add(build_page(path, &out_file, config, cache));
+    Ok(summary)
+}
+
+// Builds the input, only transpiling pages that changed since the last
+// build, and reports every failure followed by a summary. Returns whether
+// all pages were built.
+fn build(path: &Path, output: &Path, config: &Config, format: ErrorFormat) -> bool {
+    let start = Instant::now();
+    let cache = BuildCache::for_output(path, output);
+
+    let mut summary = transpile_from_to(path, output, config, &cache)
+        .unwrap_or_else(|e| Summary { errors: vec![e], ..Default::default() });
+
+    if let Err(e) = cache.save() {
+        summary.errors.push(e);
     }
 
-    transpile_file(path, output)
+    report_diagnostics(&summary.diagnostics(), format);
+    eprintln!("{}", summary.line(start.elapsed()));
+    summary.errors.is_empty()
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let format = cli.error_format;
 
     match cli.command {
-        Commands::Convert { path, output } => {
+        Commands::Convert { path, output, out } => {
+            let output = out.or(output);
+            let config = load_config()?;
+
+            if path == Path::new(STDIO) || output.as_deref().is_none_or(|output| output == Path::new(STDIO)) {
+                match convert_stream(&path, output.as_deref(), &config, std::io::stdin(), std::io::stdout()) {
+                    Ok(warnings) => report_diagnostics(&warnings, format),
+                    Err(e) => {
+                        report_error(&e, format);
+                        std::process::exit(1);
+                    },
+                }
+                return Ok(());
+            }
+
+            let output = output.unwrap();
             if path.is_dir() && output.is_file() {
                 eprintln!("Cannot output directory to a file");
                 return Ok(());
             }
-            transpile_from_to(&path, &output)?;
+            if !build(&path, &output, &config, format) {
+                std::process::exit(1);
+            }
         },
         Commands::Watch { path, output } => {
             if path.is_dir() && output.is_file() {
                 eprintln!("Cannot output directory to a file");
                 return Ok(());
             }
-            if let Err(e) = transpile_from_to(&path, &output) {
-                eprintln!("{e}");
-            }
+            let config = load_config()?;
+            build(&path, &output, &config, format);
             println!("Watching '{}'. Press CTRL-C to quit", path.to_str().unwrap());
-            watch(&path, &output)?;
+            watch::watch(&path, &output, config, format)?;
+        },
+        Commands::Build => {
+            let config = Config::discover(&std::env::current_dir()?)?
+                .with_context(|| format!("Could not find '{}' in this directory or any parent", config::CONFIG_FILE))?;
+            if !build(&config.source, &config.output, &config, format) {
+                std::process::exit(1);
+            }
         },
+        Commands::Check { paths } => check_project(paths, false, format)?,
+        Commands::Lint { paths } => check_project(paths, true, format)?,
+        Commands::Serve { path, port } => {
+            if !path.is_dir() {
+                eprintln!("Can only serve directories");
+                return Ok(());
+            }
+            let config = load_config()?;
+            let server = serve::Server::bind(&path, port, config)?;
+            let reloader = server.reloader();
+            println!("Serving '{}' at http://{}. Press CTRL-C to quit", path.display(), server.local_addr()?);
+            std::thread::spawn(move || server.run());
+            serve::watch(&path, &reloader)?;
+        },
+        Commands::Ast { path } => {
+            if let Err(e) = dump_ast(&path, std::io::stdin(), std::io::stdout()) {
+                report_error(&e, format);
+                std::process::exit(1);
+            }
+        },
+        Commands::Tokens { path } => {
+            if let Err(e) = dump_tokens(&path, std::io::stdin(), std::io::stdout()) {
+                report_error(&e, format);
+                std::process::exit(1);
+            }
+        },
+        Commands::Explain { code } => {
+            let text = explain::explain(&code)
+                .with_context(|| format!("'{code}' is not a valid error code or lint rule"))?;
+            print!("{text}");
+
+            // Lint diagnostics use the name of their rule as the code
+            if let Some(rule) = lint::RULES.iter().find(|rule| rule.name.eq_ignore_ascii_case(&code)) {
+                println!("\nSet its level with `{} = \"off\"`, `\"warn\"` or `\"error\"` in the [lint] section of nhtml.toml.", rule.name);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn check_file(path: &Path, config: &Config) -> Result<Vec<Diagnostic>> {
+    let input = std::fs::read_to_string(path)
+        .context(format!("Failed to read '{}'", path.display()))?;
+    check(&input, Some(path), &config.options_for(path), &config.lint)
+        .with_context(|| FileError(path.to_path_buf()))
+}
+
+// Checks the paths, or the whole project if there are none, and exits with an
+// error if any page fails
+fn check_project(paths: Vec<PathBuf>, strict: bool, format: ErrorFormat) -> Result<()> {
+    let config = load_config()?;
+    let paths = match (paths.is_empty(), &config.file) {
+        (false, _) => paths,
+        (true, Some(_)) => vec![config.source.clone()],
+        (true, None) => vec![PathBuf::from(".")],
+    };
+
+    if !check_paths(&paths, &config, strict, format) {
+        std::process::exit(1);
     }
 
     Ok(())
 }
 
-fn watch(input: &Path, output: &Path) -> Result<()> {
-    use notify::{Watcher, RecursiveMode};
-    use notify_debouncer_full::new_debouncer;
+// Checks every page in the files and directories, finding pages the same way
+// directory builds do, and reports every error and lint warning followed by
+// a summary. Returns whether all pages are valid, which in strict mode means
+// there are no warnings either.
+fn check_paths(paths: &[PathBuf], config: &Config, strict: bool, format: ErrorFormat) -> bool {
+    let start = Instant::now();
+    let mut pages = vec![];
+    let mut errors = vec![];
+
+    for path in paths {
+        if !path.is_dir() {
+            pages.push(path.clone());
+            continue;
+        }
 
-    let (tx, rx) = std::sync::mpsc::channel();
+        let mut files = Files::default();
+        match find_files(path, path, &config.output, config, &mut files) {
+            Ok(()) => pages.extend(files.pages.into_iter().map(|(page, _)| page)),
+            Err(e) => errors.push(e),
+        }
+    }
 
-    let mut debouncer = new_debouncer(Duration::from_millis(250), None, tx)?;
+    pages.sort();
+    pages.dedup();
 
-    debouncer.watcher().watch(&input, RecursiveMode::Recursive)
-        .context("Failed to start watcher on path")?;
+    let mut diags: Vec<Diagnostic> = errors.iter().map(to_diagnostic).collect();
+    let mut failed = errors.len();
 
-    for res in rx {
+    for res in run_parallel(&pages, |page| check_file(page, config)) {
         match res {
-            Ok(events) => {
-                events.iter().for_each(|event| {
-                    if let Err(e) = watch_event(&event, input, output) {
-                        eprintln!("{e}");
-                    }
-                })
+            Ok(found) => {
+                if found.iter().any(|d| strict || d.severity == Severity::Error) {
+                    failed += 1;
+                }
+                diags.extend(found);
+            },
+            Err(e) => {
+                failed += 1;
+                diags.push(to_diagnostic(&e));
             },
-            Err(err) => println!("err: {err:?}"),
         }
     }
 
-    Ok(())
-}
+    let warnings = diags.iter().filter(|d| d.severity == Severity::Warning).count();
 
-fn watch_event(event: &notify::Event, input: &Path, output: &Path) -> Result<()> {
-    let path = &event.paths[0];
+    report_diagnostics(&diags, format);
+    eprintln!("Checked {} files, {} failed, {} warnings in {:.2?}", pages.len(), failed, warnings, start.elapsed());
+    failed == 0
+}
 
+// Converts a single file, or stdin if the path is `-`, and writes the result
+// to the output or to stdout if there is none or it is `-`. In an output
+// directory the result is named after the input, or `stdin.html`. Errors in
+// stdin are reported as being in `<stdin>`.
+fn convert_stream(path: &Path, output: Option<&Path>, config: &Config, stdin: impl Read, mut stdout: impl Write) -> Result<Vec<Diagnostic>> {
     if path.is_dir() {
-        return Ok(());
+        anyhow::bail!("Cannot output directory to stdout, give an output directory");
+    }
+    let (input, name) = read_stream(path, stdin)?;
+
+    let (html, warnings) = transpile_with_warnings(&input, Some(&name), &config.options_for(path), &config.lint)
+        .with_context(|| FileError(name))?;
+
+    match output.filter(|output| *output != Path::new(STDIO)) {
+        Some(output) => {
+            let output = if output.is_dir() {
+                let file_name = if path == Path::new(STDIO) {
+                    OsStr::new("stdin")
+                } else {
+                    path.file_name().context("Failed to get file name")?
+                };
+                output.join(file_name).with_extension("html")
+            } else {
+                output.to_path_buf()
+            };
+
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&output, html)
+                .context(format!("Failed to write '{}'", output.display()))?;
+        },
+        None => stdout.write_all(html.as_bytes())
+            .context("Failed to write to stdout")?,
     }
 
-    if !matches!(event.kind, notify::EventKind::Modify(_)) {
-        return Ok(());
+    Ok(warnings)
+}
+
+// Reads the file, or stdin if the path is `-`, with the name errors in it are
+// reported at
+fn read_stream(path: &Path, mut stdin: impl Read) -> Result<(String, PathBuf)> {
+    if path == Path::new(STDIO) {
+        let mut input = String::new();
+        stdin.read_to_string(&mut input)
+            .context("Failed to read from stdin")?;
+        return Ok((input, PathBuf::from("<stdin>")));
     }
 
-    if !check_extension(&path, "nhtml") {
-        return Ok(());
+    let input = std::fs::read_to_string(path)
+        .context(format!("Failed to read '{}'", path.display()))?;
+    Ok((input, path.to_path_buf()))
+}
+
+// Prints the elements the parser produced, with the position of every node.
+// The page is read from stdin if the path is `-`.
+fn dump_ast(path: &Path, stdin: impl Read, stdout: impl Write) -> Result<()> {
+    let (input, name) = read_stream(path, stdin)?;
+    let mut scanner = Scanner::new(&input, Some(&name));
+    let elements = nhtml::parser::Parser::parse(&mut scanner)
+        .with_context(|| FileError(name.clone()))?;
+
+    print_json(&elements, stdout)
+}
+
+// Prints every token up to the end of the page or the first error
+fn dump_tokens(path: &Path, stdin: impl Read, stdout: impl Write) -> Result<()> {
+    let (input, name) = read_stream(path, stdin)?;
+    let mut scanner = Scanner::new(&input, Some(&name));
+
+    let mut tokens = vec![];
+    while let Some(token) = scanner.scan().with_context(|| FileError(name.clone()))? {
+        tokens.push(token);
     }
 
-    let cwd = std::env::current_dir()?;
-    let base_path: PathBuf = if input.is_absolute() { input.to_path_buf() } else { cwd.join(input) };
+    print_json(&tokens, stdout)
+}
 
-    if !output.exists() || output.is_file() {
-        println!("changes detected: {} -> {}", input.display(), output.display());
+fn print_json(value: &impl serde::Serialize, mut stdout: impl Write) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    writeln!(stdout, "{json}").context("Failed to write to stdout")
+}
 
-        transpile_file(
-            &path,
-            &output
-        )?;
-    } else {
-        let relative_out = path.strip_prefix(&base_path)?;
-        let out = output.join(relative_out)
-            .with_extension("html");
-        println!("{} {} {} {}", path.display(), base_path.display(), out.display(), relative_out.display());
+// The configuration of the project the current directory is in, if any
+fn load_config() -> Result<Config> {
+    let config = Config::discover(&std::env::current_dir()?)?;
+    Ok(config.unwrap_or_default())
+}
+
+fn report_error(err: &anyhow::Error, format: ErrorFormat) {
+    report_errors(std::slice::from_ref(err), format);
+}
+
+// Reports several errors at once. SARIF output is a single log holding all
+// of them.
+fn report_errors(errs: &[anyhow::Error], format: ErrorFormat) {
+    let diags: Vec<Diagnostic> = errs.iter().map(to_diagnostic).collect();
+    report_diagnostics(&diags, format);
+}
 
-        transpile_file(
-            &path,
-            &out
-        )?;
+fn to_diagnostic(err: &anyhow::Error) -> Diagnostic {
+    let diag = Diagnostic::from_error(err);
+    if diag.path.is_some() {
+        return diag;
+    }
+
+    // Errors without a position in the source, like failing to read it,
+    // are still reported against the file
+    let path = err.downcast_ref::<FileError>().map(|e| e.0.as_path());
+    diag.with_path(path)
+}
+
+fn report_diagnostics(diags: &[Diagnostic], format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => {
+            let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            for diag in diags {
+                eprint!("{}", render::render(diag, render::Style { color }));
+            }
+        },
+        ErrorFormat::Json => diags.iter().for_each(|diag| println!("{}", diag.to_json())),
+        ErrorFormat::Sarif if diags.is_empty() => {},
+        ErrorFormat::Sarif => println!("{:#}", diagnostic::to_sarif(diags)),
     }
-    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::transpile;
+    use std::{collections::BTreeMap, path::Path};
+
+    use nhtml::{attributes::MergeRules, variables::Variable, config::{Config, Options}, diagnostic::Diagnostic, emitter::EmitOptions, lint::{Level, LintConfig}, parser::{Element, Parser}, scanner::Scanner, transpile, transpile_with, transpile_with_warnings};
+
+    use crate::{cache::BuildCache, check_paths, temp_dir::TempDir, convert_stream, dump_ast, dump_tokens, to_diagnostic, ErrorFormat, transpile_dir, FileError};
 
     #[test]
     fn test_html_template() {
@@ -237,7 +649,7 @@ mod tests {
         <a class="test">
             Hello
         </a>
-        <a class='test'>
+        <a class="test">
             Hello
         </a>
         <p>
@@ -254,4 +666,276 @@ mod tests {
             assert_eq!(Some(line), expect_lines.next(), "Mismatch on line {}", i + 1)
         }
     }
+
+    #[test]
+    fn test_unicode_text() {
+        let res = transpile("p 'Café'; p 'ü';").unwrap();
+        assert_eq!(res, "<p>\n    Café\n</p>\n<p>\n    ü\n</p>\n");
+    }
+
+    #[test]
+    fn test_comments() {
+        let res = transpile("// comment\np;/* a\n * b */p;// end").unwrap();
+        assert_eq!(res, "<p></p>\n<p></p>\n");
+    }
+
+    #[test]
+    fn test_framework_and_namespaced_names() {
+        let src = r##"
+            svg xmlns:xlink="http://www.w3.org/1999/xlink" {
+                use xlink:href="#icon";
+            }
+            button @click.prevent="open = true" :class="cls" x-on:keyup.enter="go" x-föo;
+        "##;
+
+        let expect = r##"<svg xmlns:xlink="http://www.w3.org/1999/xlink">
+    <use xlink:href="#icon"></use>
+</svg>
+<button @click.prevent="open = true" :class="cls" x-on:keyup.enter="go" x-föo></button>
+"##;
+
+        assert_eq!(transpile(src).unwrap(), expect);
+    }
+
+    #[test]
+    fn test_emit_options_and_variables() {
+        let opts = Options {
+            emit: EmitOptions { indent: 2, ..EmitOptions::default() },
+            variables: BTreeMap::from([("name".to_owned(), Variable::Text("nhtml".to_owned()))]),
+            ..Options::default()
+        };
+        let res = transpile_with("div { p title='{{ name }}' 'Hi {{ name }}'; }", None, &opts).unwrap();
+        assert_eq!(res, "<div>\n  <p title=\"nhtml\">\n    Hi nhtml\n  </p>\n</div>\n");
+
+        let opts = Options { emit: EmitOptions { minify: true, ..opts.emit }, ..opts };
+        let res = transpile_with("div { p 'Hi {{ name }}'; br; }", None, &opts).unwrap();
+        assert_eq!(res, "<div><p>Hi nhtml</p><br></br></div>");
+    }
+
+    #[test]
+    fn test_attribute_values() {
+        let opts = Options {
+            variables: BTreeMap::from([
+                ("home".to_owned(), Variable::Text("/index.html".to_owned())),
+                ("locked".to_owned(), Variable::Bool(false)),
+                ("label".to_owned(), Variable::Text("false".to_owned())),
+            ]),
+            ..Options::default()
+        };
+        let src = r#"input tabindex=0 size=1.5 type=text required=true hidden=false disabled={locked} value='say "hi"'; a href={home} "Home";"#;
+        let res = transpile_with(src, None, &opts).unwrap();
+        assert_eq!(res, "<input tabindex=\"0\" size=\"1.5\" type=\"text\" required value=\"say &quot;hi&quot;\"></input>\n<a href=\"/index.html\">\n    Home\n</a>\n");
+
+        // Strings stay strings, even when they read `false`
+        let res = transpile_with("p title={label};", None, &opts).unwrap();
+        assert_eq!(res, "<p title=\"false\"></p>\n");
+
+        let err = transpile_with("a href={missing};", None, &opts).unwrap_err();
+        assert!(err.to_string().starts_with("error[E0009]: Unknown variable 'missing' at 1:9 to 1:15\n"));
+
+        // Attributes that aren't boolean keep `true` and `false` as text
+        let src = "div aria-hidden=true data-x=true spellcheck=false draggable=false aria-expanded={locked} contenteditable=true;";
+        let res = transpile_with(src, None, &opts).unwrap();
+        assert_eq!(res, "<div aria-hidden=\"true\" data-x=\"true\" spellcheck=\"false\" draggable=\"false\" aria-expanded=\"false\" contenteditable=\"true\"></div>\n");
+
+        let res = transpile_with("input type=hidden name=flag value=false; option value=true; meta content=true;", None, &opts).unwrap();
+        assert_eq!(res, "<input type=\"hidden\" name=\"flag\" value=\"false\"></input>\n<option value=\"true\"></option>\n<meta content=\"true\">\n");
+    }
+
+    #[test]
+    fn test_spreads_and_maps() {
+        let opts = Options {
+            variables: BTreeMap::from([
+                ("button".to_owned(), Variable::Attributes(BTreeMap::from([
+                    ("class".to_owned(), Variable::Text("btn".to_owned())),
+                    ("type".to_owned(), Variable::Text("button".to_owned())),
+                ]))),
+                ("active".to_owned(), Variable::Bool(true)),
+                ("color".to_owned(), Variable::Text("red".to_owned())),
+            ]),
+            ..Options::default()
+        };
+
+        let src = "button ...button type=submit class={btn: true, active: active, hidden: false} style={color: color, 'margin-top': '1em'} 'Go';";
+        let res = transpile_with(src, None, &opts).unwrap();
+        assert_eq!(res, "<button class=\"btn active\" type=\"submit\" style=\"color: red; margin-top: 1em\">\n    Go\n</button>\n");
+
+        // Builds warn about the `type` that replaced the one from the spread
+        let (html, warnings) = transpile_with_warnings(src, None, &opts, &LintConfig::default()).unwrap();
+        assert_eq!(html, res);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].code, warnings[0].message.as_str()), (Some("duplicate-attribute"), "`type` is set more than once"));
+
+        let lint = LintConfig::new(BTreeMap::from([("duplicate-attribute".to_owned(), Level::Off)])).unwrap();
+        assert!(transpile_with_warnings(src, None, &opts, &lint).unwrap().1.is_empty());
+
+        let err = transpile_with("button ...color;", None, &opts).unwrap_err();
+        assert!(err.to_string().starts_with("error[E0010]: Variable 'color' is not a table of attributes"));
+
+        let err = transpile_with("a href={button};", None, &opts).unwrap_err();
+        assert!(err.to_string().starts_with("error[E0010]: Variable 'button' is not a value"));
+
+        assert!(transpile_with("a href={x: y};", None, &opts).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_attributes() {
+        let res = transpile("p class='a b' style='color: red;' class='b c' style='margin: 0';").unwrap();
+        assert_eq!(res, "<p class=\"a b c\" style=\"color: red; margin: 0\"></p>\n");
+
+        let err = transpile("p id='a'\n  ID='b';").unwrap_err();
+        assert!(err.to_string().starts_with("error[E0011]: Duplicate attribute 'ID' at 2:3 to 2:8"));
+        let diag = Diagnostic::from_error(&err);
+        assert_eq!(diag.labels[1].pos.start_ln, 1);
+
+        let opts = Options {
+            merge: MergeRules::new(BTreeMap::from([("rel".to_owned(), " ".to_owned()), ("class".to_owned(), String::new())])),
+            ..Options::default()
+        };
+        let res = transpile_with("a rel='noopener' rel='external';", None, &opts).unwrap();
+        assert_eq!(res, "<a rel=\"noopener external\"></a>\n");
+        assert!(transpile_with("a class='x' class='y';", None, &opts).is_err());
+    }
+
+    #[test]
+    fn test_errors_include_path() {
+        let path = Path::new("src/about.nhtml");
+        let err = transpile_with("p {\n  a $;\n}", Some(path), &Options::default()).unwrap_err();
+        assert!(err.to_string().contains("at src/about.nhtml:2:5"));
+        assert_eq!(Diagnostic::from_error(&err).path.as_deref(), Some(path));
+
+        let err = transpile_with("div {", Some(path), &Options::default()).unwrap_err();
+        assert!(err.to_string().contains("at src/about.nhtml:1:6"));
+    }
+
+    #[test]
+    fn test_check_writes_nothing() {
+        let root = TempDir::new("check");
+        std::fs::create_dir_all(root.join("blog")).unwrap();
+        std::fs::write(root.join("index.nhtml"), "p 'Home';").unwrap();
+        std::fs::write(root.join("blog/post.nhtml"), "p 'Post';").unwrap();
+        let paths = [root.to_path_buf()];
+
+        assert!(check_paths(&paths, &Config::default(), false, ErrorFormat::Json));
+        assert!(!root.join("index.html").exists());
+
+        // Lint warnings only fail in strict mode
+        std::fs::write(root.join("blog/post.nhtml"), "img src='a.png';").unwrap();
+        assert!(check_paths(&paths, &Config::default(), false, ErrorFormat::Json));
+        assert!(!check_paths(&paths, &Config::default(), true, ErrorFormat::Json));
+
+        std::fs::write(root.join("blog/post.nhtml"), "p {").unwrap();
+        assert!(!check_paths(&paths, &Config::default(), false, ErrorFormat::Json));
+    }
+
+    #[test]
+    fn test_transpile_dir_reports_every_error() {
+        let root = TempDir::new("dir");
+        std::fs::create_dir_all(root.join("src/blog")).unwrap();
+
+        std::fs::write(root.join("src/a.nhtml"), "p $;").unwrap();
+        std::fs::write(root.join("src/b.nhtml"), "p 'B';").unwrap();
+        std::fs::write(root.join("src/blog/c.nhtml"), "div {").unwrap();
+        std::fs::write(root.join("src/blog/style.css"), "p {}").unwrap();
+
+        let (src, out) = (root.join("src"), root.join("out"));
+        let cache = BuildCache::load(&out);
+        let summary = transpile_dir(&src, &src, &out, &Config::default(), &cache).unwrap();
+
+        let failed: Vec<_> = summary.errors.iter()
+            .map(|e| e.downcast_ref::<FileError>().unwrap().0.clone())
+            .collect();
+        assert_eq!(failed, [src.join("a.nhtml"), src.join("blog/c.nhtml")]);
+        assert_eq!((summary.built, summary.copied), (1, 1));
+        assert!(out.join("b.html").exists());
+        assert!(out.join("blog/style.css").exists());
+
+        let summary = transpile_dir(&src, &src, &out, &Config::default(), &cache).unwrap();
+        assert_eq!((summary.built, summary.copied, summary.fresh, summary.errors.len()), (0, 0, 2, 2));
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_plugins_are_dependencies() {
+        let root = TempDir::new("plugins");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/index.nhtml"), "p 'Home';").unwrap();
+        std::fs::write(root.join("nhtml.toml"), "[[plugins]]\nname = 'check'\npath = 'check.wasm'\n").unwrap();
+        std::fs::write(root.join("check.wasm"), include_bytes!("../tests/fixtures/echo.wasm")).unwrap();
+
+        let (src, out) = (root.join("src"), root.join("out"));
+        let cache = BuildCache::load(&out);
+        let config = Config::load(&root.join("nhtml.toml")).unwrap();
+        assert_eq!(transpile_dir(&src, &src, &out, &config, &cache).unwrap().built, 1);
+
+        // A rebuilt plugin builds the page again, even if nhtml.toml is the same
+        std::fs::write(root.join("check.wasm"), include_bytes!("../tests/fixtures/reject.wasm")).unwrap();
+        let config = Config::load(&root.join("nhtml.toml")).unwrap();
+        let summary = transpile_dir(&src, &src, &out, &config, &cache).unwrap();
+        assert_eq!((summary.fresh, summary.errors.len()), (0, 1));
+    }
+
+    #[test]
+    fn test_convert_stream() {
+        let root = TempDir::new("stream");
+        std::fs::create_dir_all(root.join("out")).unwrap();
+        std::fs::write(root.join("page.nhtml"), "p 'Page';").unwrap();
+        let (config, stdin) = (Config::default(), Path::new("-"));
+
+        let mut stdout = vec![];
+        convert_stream(stdin, None, &config, "p 'Hi';".as_bytes(), &mut stdout).unwrap();
+        assert_eq!(String::from_utf8(stdout).unwrap(), "<p>\n    Hi\n</p>\n");
+
+        let mut stdout = vec![];
+        convert_stream(stdin, Some(Path::new("-")), &config, "br;".as_bytes(), &mut stdout).unwrap();
+        assert_eq!(String::from_utf8(stdout).unwrap(), "<br></br>\n");
+
+        let mut stdout = vec![];
+        convert_stream(&root.join("page.nhtml"), None, &config, std::io::empty(), &mut stdout).unwrap();
+        assert_eq!(String::from_utf8(stdout).unwrap(), "<p>\n    Page\n</p>\n");
+
+        // Output directories get a file named after the input
+        convert_stream(stdin, Some(&root.join("out")), &config, "hr;".as_bytes(), std::io::sink()).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("out/stdin.html")).unwrap(), "<hr></hr>\n");
+
+        let err = convert_stream(stdin, None, &config, "p {".as_bytes(), std::io::sink()).unwrap_err();
+        assert_eq!(to_diagnostic(&err).path.unwrap(), Path::new("<stdin>"));
+        assert!(format!("{err:#}").starts_with("Failed to transpile '<stdin>'"));
+    }
+
+    #[test]
+    fn test_ast_and_tokens_json() {
+        let src = "div class={a: true} { img src='a.png' hidden; 'Hi' }";
+        let mut scanner = Scanner::new(src, None);
+        let elements = Parser::parse(&mut scanner).unwrap();
+
+        // The tree can be read back as it was written
+        let json = serde_json::to_string(&elements).unwrap();
+        let parsed: Vec<Element> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        assert!(json.starts_with(r#"[{"type":"tag","ty":{"node":"div","pos":{"idx":0,"len":3,"start_ln":1,"start_cn":1"#));
+
+        let mut scanner = Scanner::new("a {", None);
+        let token = serde_json::to_value(scanner.scan().unwrap()).unwrap();
+        assert_eq!(token["ty"], "text");
+        let token = serde_json::to_value(scanner.scan().unwrap()).unwrap();
+        assert_eq!(token["ty"], "left_brace");
+        assert_eq!(token["pos"]["start_cn"], 3);
+
+        // The commands print the same JSON for a page read from stdin
+        let stdin = Path::new("-");
+        let mut stdout = vec![];
+        dump_ast(stdin, src.as_bytes(), &mut stdout).unwrap();
+        let printed: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        assert_eq!(printed, serde_json::to_value(&elements).unwrap());
+
+        let mut stdout = vec![];
+        dump_tokens(stdin, "a {".as_bytes(), &mut stdout).unwrap();
+        let printed: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        let types: Vec<_> = printed.as_array().unwrap().iter().map(|token| token["ty"].as_str().unwrap()).collect();
+        assert_eq!(types, ["text", "left_brace"]);
+
+        let err = dump_ast(stdin, "div {".as_bytes(), vec![]).unwrap_err();
+        assert_eq!(to_diagnostic(&err).path.unwrap(), Path::new("<stdin>"));
+    }
 }
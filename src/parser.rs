@@ -1,34 +1,202 @@
-use crate::{token::{Token, TokenType}, scanner::{Scanner, ScanError}, position::ErrorDisplay};
+use crate::{token::{Token, TokenType}, scanner::{Scanner, ScanError}, position::{ErrorDisplay, Position, Spanned}, diagnostic::Diagnostic};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParseError {
-    #[error("{0} at {1}")]
     UnexpectedToken(String, ErrorDisplay),
-
-    #[error("Expected element at {0}")]
     ExpectedElement(ErrorDisplay),
+    UnclosedBlock(ErrorDisplay, Position),
+    UnknownVariable(String, ErrorDisplay),
+    VariableType(String, &'static str, ErrorDisplay),
+    DuplicateAttribute(String, ErrorDisplay, Position),
+    // The name of the transform and what it reported, followed by the other
+    // problems it found
+    Transform(String, String, ErrorDisplay, Vec<(String, Option<Position>)>),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("error[{}]: {} at {}", self.code(), self.message(), self.display()))
+    }
 }
 
-#[derive(Debug)]
+impl ParseError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken(..) => "E0006",
+            ParseError::ExpectedElement(_) => "E0007",
+            ParseError::UnclosedBlock(..) => "E0008",
+            ParseError::UnknownVariable(..) => "E0009",
+            ParseError::VariableType(..) => "E0010",
+            ParseError::DuplicateAttribute(..) => "E0011",
+            ParseError::Transform(..) => "E0012",
+        }
+    }
+
+    // The error message without the source snippet
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::UnexpectedToken(msg, _) => msg.clone(),
+            ParseError::ExpectedElement(_) => "Expected element".to_owned(),
+            ParseError::UnclosedBlock(..) => "Unclosed block".to_owned(),
+            ParseError::UnknownVariable(name, _) => format!("Unknown variable '{name}'"),
+            ParseError::VariableType(name, expected, _) => format!("Variable '{name}' is not {expected}"),
+            ParseError::DuplicateAttribute(name, ..) => format!("Duplicate attribute '{name}'"),
+            ParseError::Transform(_, msg, ..) => msg.clone(),
+        }
+    }
+
+    pub fn pos(&self) -> Position {
+        self.display().pos()
+    }
+
+    fn display(&self) -> &ErrorDisplay {
+        match self {
+            ParseError::UnexpectedToken(_, disp) |
+            ParseError::ExpectedElement(disp) |
+            ParseError::UnclosedBlock(disp, _) |
+            ParseError::UnknownVariable(_, disp) |
+            ParseError::VariableType(_, _, disp) |
+            ParseError::DuplicateAttribute(_, disp, _) => disp,
+            ParseError::Transform(_, _, disp, _) => disp,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let pos = self.pos();
+        let diag = Diagnostic::error(Some(self.code()), self.message())
+            .with_path(self.display().path())
+            .with_source(self.display().source());
+
+        match self {
+            ParseError::UnexpectedToken(..) => diag
+                .with_label(pos, None),
+            ParseError::ExpectedElement(_) => diag
+                .with_label(pos, Some("expected a tag, string, raw HTML, JS or CSS block")),
+            ParseError::UnclosedBlock(_, open) => diag
+                .with_label(pos, Some("expected `}`"))
+                .with_secondary_label(*open, "unclosed `{` opened here")
+                .with_insertion("add `}` to close the block", pos, "}"),
+            ParseError::UnknownVariable(..) => diag
+                .with_label(pos, Some("not set in nhtml.toml"))
+                .with_note("variables are set in the [variables] section of nhtml.toml"),
+            ParseError::VariableType(..) => diag
+                .with_label(pos, None)
+                .with_note("tables of attributes can only be spread with `...name`, other variables are used as `{name}`"),
+            ParseError::DuplicateAttribute(_, _, first) => diag
+                .with_label(pos, Some("set again here"))
+                .with_secondary_label(*first, "first set here")
+                .with_note("only attributes with a merge rule, like `class` and `style`, can be set more than once")
+                .with_replacement("remove one of them", pos, ""),
+            ParseError::Transform(name, _, _, related) => related.iter()
+                .fold(diag.with_label(pos, Some(&format!("reported by `{name}`"))), |diag, (message, pos)| match pos {
+                    Some(pos) => diag.with_secondary_label(*pos, message),
+                    None => diag.with_note(message),
+                }),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Tag {
-    pub ty: String,
-    pub attribs: Vec<(String, Option<String>)>,
+    pub ty: Spanned<String>,
+    // From the tag name to the end of the body
+    #[serde(default)]
+    pub pos: Position,
+    pub attribs: Vec<Attrib>,
     pub body: Vec<Element>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Attrib {
+    // `name` or `name=value`
+    Named {
+        name: Spanned<String>,
+        value: Option<Spanned<Value>>,
+    },
+    // `...name`, the attributes of a variable. They are filled in when
+    // variables are replaced.
+    Spread {
+        name: Spanned<String>,
+        #[serde(default)]
+        attribs: Vec<(String, Option<Value>)>,
+    },
+}
+
+impl Attrib {
+    // The position of all of the attribute, or of the name of a spread
+    pub fn pos(&self) -> Position {
+        match self {
+            Attrib::Named { name, value: Some(value) } => name.pos.to(&value.pos),
+            Attrib::Named { name, value: None } |
+            Attrib::Spread { name, .. } => name.pos,
+        }
+    }
+}
+
+// The value of an attribute after `=`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum Value {
+    // A quoted string, without its quotes
+    String(String),
+    // An unquoted value starting with a digit, like `0` or `1.5`
+    Number(String),
+    // An unquoted name, like `index.html`
+    Ident(String),
+    // `true` emits the attribute without a value, `false` leaves it out
+    Bool(bool),
+    // `{name}`, replaced by the value of the variable before emitting. The
+    // position is the one of the name.
+    Variable(Spanned<String>),
+    // `{key: value, ...}` for `class` and `style`. Names in the values are
+    // variables.
+    Map(Vec<(Spanned<String>, Spanned<Value>)>),
+}
+
+impl Value {
+    // The text of the value, if it has one
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(text) |
+            Value::Number(text) |
+            Value::Ident(text) => Some(text),
+            Value::Bool(_) |
+            Value::Variable(_) |
+            Value::Map(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
 pub enum Element {
     Tag(Tag),
-    Text(String),
-    HTML(String),
-    Js(String),
-    Css(String),
+    Text(Spanned<String>),
+    Html(Spanned<String>),
+    Js(Spanned<String>),
+    Css(Spanned<String>),
+}
+
+impl Element {
+    pub fn pos(&self) -> Position {
+        match self {
+            Element::Tag(tag) => tag.pos,
+            Element::Text(text) |
+            Element::Html(text) |
+            Element::Js(text) |
+            Element::Css(text) => text.pos,
+        }
+    }
 }
 
 pub struct Parser<'a> {
     scanner: &'a mut Scanner<'a>,
     unused: Vec<Token>,
+    // The position of the last token that was taken
+    last: Option<Position>,
 }
 
 impl<'a> Parser<'a> {
@@ -36,6 +204,7 @@ impl<'a> Parser<'a> {
         let mut parser = Parser {
             scanner,
             unused: vec![],
+            last: None,
         };
 
         let mut tags = vec![];
@@ -48,24 +217,24 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_element(&mut self) -> Result<Option<Element>> {
-        if self.is_next(TokenType::Text) {
+        if self.is_next(TokenType::Text)? {
             let tag = self.parse_tag()?;
             Ok(Some(Element::Tag(tag)))
-        } else if self.is_next(TokenType::String) {
+        } else if self.is_next(TokenType::String)? {
             let string = self.take()?.unwrap();
             let value = string.lexeme[1..string.lexeme.len() - 1].to_owned();
-            Ok(Some(Element::Text(value)))
-        } else if self.is_next(TokenType::Html) {
+            Ok(Some(Element::Text(Spanned::new(value, string.pos))))
+        } else if self.is_next(TokenType::Html)? {
             let html = self.take()?.unwrap();
-            Ok(Some(Element::HTML(html.lexeme)))
-        } else if self.is_next(TokenType::Js) {
+            Ok(Some(Element::Html(Spanned::new(html.lexeme, html.pos))))
+        } else if self.is_next(TokenType::Js)? {
             let js = self.take()?.unwrap();
             let source = &js.lexeme[3..js.lexeme.len() - 1];
-            Ok(Some(Element::Js(source.to_string())))
-        } else if self.is_next(TokenType::Css) {
+            Ok(Some(Element::Js(Spanned::new(source.to_string(), js.pos))))
+        } else if self.is_next(TokenType::Css)? {
             let css = self.take()?.unwrap();
             let source = &css.lexeme[4..css.lexeme.len() - 1];
-            Ok(Some(Element::Css(source.to_string())))
+            Ok(Some(Element::Css(Spanned::new(source.to_string(), css.pos))))
         } else {
             Ok(None)
         }
@@ -75,15 +244,16 @@ impl<'a> Parser<'a> {
         let ty = self.expect(TokenType::Text, "Expected element name")?;
         let mut attribs = vec![];
 
-        if self.is_next(TokenType::Text) {
-            while !(self.is_next(TokenType::LeftBrace) || self.is_next(TokenType::Semicolon) || self.is_next(TokenType::String)) {
+        if self.is_next(TokenType::Text)? || self.is_next(TokenType::Spread)? {
+            while !(self.is_next(TokenType::LeftBrace)? || self.is_next(TokenType::Semicolon)? || self.is_next(TokenType::String)?) {
                 attribs.push(self.parse_attrib()?);
             }
         }
 
         let body = self.parse_body()?;
         let tag = Tag {
-            ty: ty.lexeme,
+            pos: ty.pos.to(&self.last.unwrap_or(ty.pos)),
+            ty: Spanned::new(ty.lexeme, ty.pos),
             attribs,
             body,
         };
@@ -91,40 +261,152 @@ impl<'a> Parser<'a> {
         Ok(tag)
     }
 
-    fn parse_attrib(&mut self) -> Result<(String, Option<String>)> {
+    fn parse_attrib(&mut self) -> Result<Attrib> {
+        if self.is_next(TokenType::Spread)? {
+            self.take()?;
+            let name = self.expect(TokenType::Text, "Expected variable name")?;
+            return Ok(Attrib::Spread { name: Spanned::new(name.lexeme, name.pos), attribs: vec![] });
+        }
+
         let id = self.expect(TokenType::Text, "Expected attribute name")?;
         
-        let value = if self.is_next(TokenType::Equal) {
+        let value = if self.is_next(TokenType::Equal)? {
             self.take()?;
-            let value = self.expect(TokenType::String, "Expected string value")?;
-            Some(value.lexeme)
+            let start = self.peek()?.map_or(id.pos, |t| t.pos);
+            let value = self.parse_value(&id.lexeme)?;
+            Some(Spanned::new(value, start.to(&self.last.unwrap_or(start))))
         } else {
             None
         };
 
-        Ok((id.lexeme, value))
+        Ok(Attrib::Named { name: Spanned::new(id.lexeme, id.pos), value })
+    }
+
+    fn parse_value(&mut self, attrib: &str) -> Result<Value> {
+        let Some(next) = self.peek()? else {
+            return Err(ParseError::UnexpectedToken("Expected attribute value".to_owned(), self.error_pos()?))?;
+        };
+
+        match next.ty {
+            TokenType::String => {
+                let string = self.take()?.unwrap();
+                Ok(Value::String(string.lexeme[1..string.lexeme.len() - 1].to_owned()))
+            },
+            TokenType::Number => Ok(Value::Number(self.take()?.unwrap().lexeme)),
+            TokenType::Text => {
+                let text = self.take()?.unwrap();
+                match text.lexeme.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Ok(Value::Ident(text.lexeme)),
+                }
+            },
+            TokenType::LeftBrace => {
+                self.take()?;
+
+                let is_map = match self.peek()? {
+                    Some(t) => t.ty == TokenType::String || (t.ty == TokenType::Text && t.lexeme.ends_with(':')),
+                    None => false,
+                };
+
+                if is_map {
+                    if !["class", "style"].iter().any(|name| attrib.eq_ignore_ascii_case(name)) {
+                        return Err(ParseError::UnexpectedToken("Only `class` and `style` can be maps".to_owned(), self.error_pos()?))?;
+                    }
+                    return self.parse_map();
+                }
+
+                let name = self.expect(TokenType::Text, "Expected variable name")?;
+                self.expect(TokenType::RightBrace, "Expected '}'")?;
+                Ok(Value::Variable(Spanned::new(name.lexeme, name.pos)))
+            },
+            _ => Err(ParseError::UnexpectedToken("Expected attribute value".to_owned(), self.error_pos()?))?,
+        }
+    }
+
+    // Parses `key: value` pairs separated by commas up to the closing `}`.
+    // The `{` has already been taken. Keys are names followed by `:`, or
+    // strings followed by a separate `:`.
+    fn parse_map(&mut self) -> Result<Value> {
+        let mut entries = vec![];
+
+        while !self.is_next(TokenType::RightBrace)? {
+            let key = if self.is_next(TokenType::String)? {
+                let string = self.take()?.unwrap();
+                if !matches!(self.peek()?, Some(t) if t.ty == TokenType::Text && t.lexeme == ":") {
+                    return Err(ParseError::UnexpectedToken("Expected ':' after the key".to_owned(), self.error_pos()?))?;
+                }
+                self.take()?;
+                Spanned::new(string.lexeme[1..string.lexeme.len() - 1].to_owned(), string.pos)
+            } else {
+                let key = self.expect(TokenType::Text, "Expected `key: value`")?;
+                let Some(name) = key.lexeme.strip_suffix(':') else {
+                    return Err(ParseError::UnexpectedToken("Expected `key: value`".to_owned(), self.scanner.pos_error(&key.pos)))?;
+                };
+
+                // The position leaves out the `:`
+                let pos = Position { len: key.pos.len - 1, end_cn: key.pos.end_cn - 1, ..key.pos };
+                Spanned::new(name.to_owned(), pos)
+            };
+
+            let Some(next) = self.peek()? else {
+                return Err(ParseError::UnexpectedToken("Expected map value".to_owned(), self.error_pos()?))?;
+            };
+
+            let pos = next.pos;
+            let value = match next.ty {
+                TokenType::String => {
+                    let string = self.take()?.unwrap();
+                    Value::String(string.lexeme[1..string.lexeme.len() - 1].to_owned())
+                },
+                TokenType::Number => Value::Number(self.take()?.unwrap().lexeme),
+                TokenType::Text => {
+                    let text = self.take()?.unwrap();
+                    match text.lexeme.as_str() {
+                        "true" => Value::Bool(true),
+                        "false" => Value::Bool(false),
+                        _ => Value::Variable(Spanned::new(text.lexeme, text.pos)),
+                    }
+                },
+                _ => return Err(ParseError::UnexpectedToken("Expected map value".to_owned(), self.error_pos()?))?,
+            };
+
+            entries.push((key, Spanned::new(value, pos)));
+
+            if self.is_next(TokenType::Comma)? {
+                self.take()?;
+            } else if !self.is_next(TokenType::RightBrace)? {
+                return Err(ParseError::UnexpectedToken("Expected ',' or '}'".to_owned(), self.error_pos()?))?;
+            }
+        }
+
+        self.take()?;
+        Ok(Value::Map(entries))
     }
 
     fn parse_body(&mut self) -> Result<Vec<Element>> {
-        if self.is_next(TokenType::Semicolon) {
+        if self.is_next(TokenType::Semicolon)? {
             self.take()?;
             return Ok(vec![]);
         }
 
-        if self.is_next(TokenType::String) {
+        if self.is_next(TokenType::String)? {
             // Should always succeed when unwrapping because of the above check
             let elem = self.parse_element()?.unwrap();
             self.expect(TokenType::Semicolon, "Expected ';'")?;
             return Ok(vec![elem])
         }
 
-        if self.is_next(TokenType::LeftBrace) {
-            self.take()?;
+        if self.is_next(TokenType::LeftBrace)? {
+            let open = self.take()?.unwrap();
 
             let mut body = vec![];
 
-            while !self.is_next(TokenType::RightBrace) {
+            while !self.is_next(TokenType::RightBrace)? {
                 let tag = self.parse_element()?;
+                if tag.is_none() && self.peek()?.is_none() {
+                    return Err(ParseError::UnclosedBlock(self.error_pos()?, open.pos))?;
+                }
                 if tag.is_none() {
                     return Err(ParseError::ExpectedElement(self.error_pos()?))?;
                 }
@@ -143,15 +425,12 @@ impl<'a> Parser<'a> {
         Ok(vec![body.unwrap()])
     }
 
-    fn is_next(&mut self, ty: TokenType) -> bool {
-        match self.peek() {
-            Ok(Some(t)) if t.ty == ty => true,
-            _ => false,
-        }
+    fn is_next(&mut self, ty: TokenType) -> Result<bool> {
+        Ok(matches!(self.peek()?, Some(t) if t.ty == ty))
     }
 
     fn expect(&mut self, expected: TokenType, msg: &str) -> Result<Token> {
-        if self.is_next(expected) {
+        if self.is_next(expected)? {
             Ok(self.take()?.unwrap())
         } else {
             Err(ParseError::UnexpectedToken(msg.to_owned(), self.error_pos()?))?
@@ -169,21 +448,20 @@ impl<'a> Parser<'a> {
     }
 
     fn take(&mut self) -> Result<Option<Token>, ScanError> {
-        if self.unused.len() > 0 {
-            return Ok(self.unused.pop());
-        }
-
-        let tok = self.scanner.scan()?;
+        let tok = match self.unused.pop() {
+            Some(tok) => Some(tok),
+            None => self.scanner.scan()?,
+        };
 
-        if tok.is_none() {
-            return Ok(None);
+        if let Some(tok) = &tok {
+            self.last = Some(tok.pos);
         }
 
-        Ok(Some(tok.unwrap()))
+        Ok(tok)
     }
 
     fn peek(&mut self) -> Result<Option<&Token>> {
-        if self.unused.len() == 0 {
+        if self.unused.is_empty() {
             let tok = self.scanner.scan()?;
 
             if tok.is_none() {
@@ -193,6 +471,6 @@ impl<'a> Parser<'a> {
             self.unused.push(tok.unwrap());
         }
 
-        Ok(self.unused.get(self.unused.len() - 1))
+        Ok(self.unused.last())
     }
 }
@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimitsBuilder, Trap};
+
+use crate::{parser::Element, position::Position, transform::{Transform, TransformError}};
+
+// A transform compiled to WebAssembly. The module exports its `memory`,
+// `alloc(len) -> ptr` to get space for the input and
+// `transform(ptr, len) -> i64`, which gets the page as JSON and returns where
+// its JSON answer is, with the pointer in the upper 32 bits and the length in
+// the lower ones.
+//
+// The page is sent as `{"elements": [...]}`. The answer is the same with the
+// changed elements, or `{"diagnostics": [...]}` with a `message` and
+// optionally a `pos` for every problem it found.
+pub struct Plugin {
+    name: String,
+    engine: Engine,
+    module: Module,
+    // Roughly the number of instructions a plugin can run for a page, so one
+    // that never returns can't hang a build
+    fuel: u64,
+    // The bytes of memory a plugin can grow to for a page
+    memory: usize,
+}
+
+const FUEL: u64 = 10_000_000_000;
+const MEMORY: usize = 256 << 20;
+
+#[derive(Serialize)]
+struct Input<'a> {
+    elements: &'a [Element],
+}
+
+#[derive(Deserialize)]
+struct Output {
+    elements: Option<Vec<Element>>,
+    #[serde(default)]
+    diagnostics: Vec<PluginDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct PluginDiagnostic {
+    message: String,
+    pos: Option<Position>,
+}
+
+impl Plugin {
+    pub fn load(name: &str, path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .context(format!("Failed to read plugin '{}'", path.display()))?;
+
+        Self::new(name, &bytes)
+            .context(format!("Failed to load plugin '{}'", path.display()))
+    }
+
+    pub fn new(name: &str, bytes: &[u8]) -> Result<Self> {
+        let engine = Engine::new(Config::new().consume_fuel(true))?;
+        let module = Module::from_binary(&engine, bytes)?;
+
+        Ok(Self { name: name.to_owned(), engine, module, fuel: FUEL, memory: MEMORY })
+    }
+
+    // Runs the plugin in a new instance, so nothing is kept between pages
+    fn call(&self, input: &[u8]) -> Result<Vec<u8>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.memory)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel)?;
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+
+        let memory = instance.get_memory(&mut store, "memory")
+            .context("Plugin doesn't export its memory")?;
+        let alloc = instance.get_typed_func::<u32, u32>(&mut store, "alloc")?;
+        let transform = instance.get_typed_func::<(u32, u32), u64>(&mut store, "transform")?;
+
+        let len = u32::try_from(input.len()).context("Page is too large for the plugin")?;
+        let ptr = alloc.call(&mut store, len).map_err(stopped)?;
+        memory.write(&mut store, ptr as usize, input)?;
+
+        let res = transform.call(&mut store, (ptr, len)).map_err(stopped)?;
+        let mut output = vec![0; (res & 0xffff_ffff) as usize];
+        memory.read(&store, (res >> 32) as usize, &mut output)?;
+
+        Ok(output)
+    }
+}
+
+// Explains why a plugin was stopped if it ran into one of its limits
+fn stopped(err: anyhow::Error) -> anyhow::Error {
+    if err.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) {
+        return anyhow!("Plugin took too long and was stopped");
+    }
+
+    // Store limits fail the call with only a message
+    if err.root_cause().to_string().starts_with("forcing trap when growing memory") {
+        return anyhow!("Plugin used too much memory and was stopped");
+    }
+
+    err
+}
+
+impl Transform for Plugin {
+    fn transform(&self, elements: &mut Vec<Element>) -> Result<()> {
+        let input = serde_json::to_vec(&Input { elements })?;
+        let output = self.call(&input)
+            .context(format!("Plugin '{}' failed", self.name))?;
+        let output: Output = serde_json::from_slice(&output)
+            .context(format!("Plugin '{}' returned an invalid answer", self.name))?;
+
+        // Every problem is reported in one error, pointing at the first one
+        // with a position
+        let mut diagnostics = output.diagnostics;
+        if !diagnostics.is_empty() {
+            let first = diagnostics.iter().position(|diag| diag.pos.is_some()).unwrap_or(0);
+            let diag = diagnostics.remove(first);
+            let related = diagnostics.into_iter().map(|diag| (diag.message, diag.pos)).collect();
+            return Err(TransformError { transform: self.name.clone(), message: diag.message, pos: diag.pos, related }.into());
+        }
+
+        match output.elements {
+            Some(changed) => *elements = changed,
+            None => bail!("Plugin '{}' returned neither elements nor diagnostics", self.name),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Plugin;
+    use crate::{config::Options, diagnostic::Diagnostic, transpile_with};
+
+    #[test]
+    fn test_plugins() {
+        // The page makes it through the plugin unchanged
+        let src = "div class={a: true} { img src='a.png' hidden; p 'Hi'; <hr> }";
+        let echo = Plugin::new("echo", include_bytes!("../tests/fixtures/echo.wasm")).unwrap();
+        let opts = Options::default().with_transform(echo);
+        assert_eq!(transpile_with(src, None, &opts).unwrap(), transpile_with(src, None, &Options::default()).unwrap());
+
+        let reject = Plugin::new("reject", include_bytes!("../tests/fixtures/reject.wasm")).unwrap();
+        let opts = Options::default().with_transform(reject);
+        let err = transpile_with("div; br;", None, &opts).unwrap_err();
+        assert_eq!(err.to_string().lines().next().unwrap(), "error[E0012]: Not allowed here at 1:1 to 1:3");
+
+        // The other problems the plugin found are reported along with it
+        let diag = Diagnostic::from_error(&err);
+        let labels: Vec<_> = diag.labels.iter().map(|label| (label.pos.start_cn, label.message.as_deref())).collect();
+        assert_eq!(labels, vec![(1, Some("reported by `reject`")), (6, Some("Not allowed either"))]);
+        assert_eq!(diag.notes, vec!["Pages need a title"]);
+
+        assert!(Plugin::new("invalid", b"not wasm").is_err());
+
+        let mut spin = Plugin::new("spin", include_bytes!("../tests/fixtures/spin.wasm")).unwrap();
+        spin.fuel = 1_000_000;
+        let opts = Options::default().with_transform(spin);
+        let err = transpile_with("div;", None, &opts).unwrap_err();
+        assert_eq!(format!("{err:#}"), "Plugin 'spin' failed: Plugin took too long and was stopped");
+
+        let mut grow = Plugin::new("grow", include_bytes!("../tests/fixtures/grow.wasm")).unwrap();
+        grow.memory = 1 << 20;
+        let opts = Options::default().with_transform(grow);
+        let err = transpile_with("div;", None, &opts).unwrap_err();
+        assert_eq!(format!("{err:#}"), "Plugin 'grow' failed: Plugin used too much memory and was stopped");
+    }
+}
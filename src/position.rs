@@ -1,7 +1,15 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
 use super::source::Source;
+use super::diagnostic::Label;
+use super::render::{render_snippet, Style};
 
-// Position represents a selection in the source code.
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+// Position represents a selection in the source code. `idx` and `len` are
+// byte offsets into the source, lines and columns are 1-based and count
+// characters, and the end column is inclusive.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub idx: usize,
     pub len: usize,
@@ -12,50 +20,85 @@ pub struct Position {
 }
 
 impl Position {
-    pub fn new() -> Self {
-        Self::default()
+    // Selects the first character of the source
+    pub fn new(source: &Source) -> Self {
+        Self {
+            idx: 0,
+            len: source.char_at(0).map_or(0, char::len_utf8),
+            start_ln: 1,
+            start_cn: 1,
+            end_ln: 1,
+            end_cn: 1,
+        }
     }
 
     // Extends the range of the position to the next character
     // taking into account newlines
-    pub fn extend(&mut self, source: &str) {
-        let char = source.get_char(&self);
-        let newline = matches!(char, Some('\n'));
-
-        self.len += 1;
-        self.end_cn += 1;
+    pub fn extend(&mut self, source: &Source) {
+        let Some(next) = source.peek_next(self) else {
+            return;
+        };
 
-        if newline {
+        if matches!(source.get_char(self), Some('\n')) {
             self.end_cn = 1;
             self.end_ln += 1;
+        } else {
+            self.end_cn += 1;
         }
+
+        self.len += next.len_utf8();
     }
 
-    // Moves the selection to the next character after the current selection
-    pub fn advance(&mut self, source: &str) {
-        self.extend(source);
-        self.idx += self.len - 1;
+    // Moves the selection to the next character after the current selection.
+    // At the end of the source the selection becomes empty.
+    pub fn advance(&mut self, source: &Source) {
+        let current = source.get_char(self);
+
+        self.idx += self.len;
+        self.len = source.char_at(self.idx).map_or(0, char::len_utf8);
+
+        match current {
+            Some('\n') => {
+                self.end_cn = 1;
+                self.end_ln += 1;
+            },
+            Some(_) => self.end_cn += 1,
+            None => (),
+        }
+
         self.start_cn = self.end_cn;
         self.start_ln = self.end_ln;
-        self.len = 1;
     }
 
-    pub fn for_error(&self, src: &str) -> ErrorDisplay {
-        ErrorDisplay(*self, src.to_owned())
+    // The position of the first character of the selection
+    pub fn start(&self, src: &str) -> Position {
+        Position {
+            len: src[self.idx..].chars().next().map_or(0, char::len_utf8),
+            end_ln: self.start_ln,
+            end_cn: self.start_cn,
+            ..*self
+        }
     }
-}
 
-impl Default for Position {
-    fn default() -> Self {
-        Self {
-            idx: 0,
-            len: 1,
-            start_ln: 1,
-            start_cn: 1,
-            end_ln: 1,
-            end_cn: 2,
+    // A selection from the start of this one to the end of the other, which
+    // comes after it. Nodes added by transforms may have no position, so an
+    // end before the start keeps this selection.
+    pub fn to(&self, end: &Position) -> Position {
+        if end.idx + end.len < self.idx {
+            return *self;
+        }
+
+        Position {
+            len: end.idx + end.len - self.idx,
+            end_ln: end.end_ln,
+            end_cn: end.end_cn,
+            ..*self
         }
     }
+
+    pub fn for_error(&self, src: &Source) -> ErrorDisplay {
+        ErrorDisplay(*self, src.text().to_owned(), src.path().map(Path::to_path_buf))
+    }
 }
 
 impl std::fmt::Display for Position {
@@ -64,39 +107,75 @@ impl std::fmt::Display for Position {
     }
 }
 
+// A node of the syntax tree with the position it was written at. Nodes
+// added by transforms may have no position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spanned<T> {
+    pub node: T,
+    #[serde(default)]
+    pub pos: Position,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, pos: Position) -> Self {
+        Self { node, pos }
+    }
+}
+
+impl<T> std::ops::Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T> std::ops::DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.node
+    }
+}
+
+impl<T: std::fmt::Display> std::fmt::Display for Spanned<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.node.fmt(f)
+    }
+}
+
+// Everything needed to show where an error is: the position, the source it
+// is in and the path of the file the source was read from
 #[derive(Debug)]
-pub struct ErrorDisplay(Position, String);
+pub struct ErrorDisplay(Position, String, Option<PathBuf>);
+
+impl ErrorDisplay {
+    pub fn pos(&self) -> Position {
+        self.0
+    }
+
+    pub fn source(&self) -> &str {
+        &self.1
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.2.as_deref()
+    }
+}
 
 impl std::fmt::Display for ErrorDisplay {
-    // A bunch of confusing string manipulation for pretty error messages
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut res = format!("{}:{}", self.0.start_ln, self.0.start_cn);
-
-        let src = &self.1[..];
-        let line_disp_len = format!("{}", self.0.end_ln).len();
-        let line_disp_padding = " ".repeat(line_disp_len);
-        let lines = src.get_lines(&self.0);
-        let arrow_pad = " ".repeat(self.0.start_cn);
-        
-        let arrows = if self.0.start_ln == self.0.end_ln {
-            "v".repeat(self.0.end_cn - self.0.start_cn + 1)
-        } else {
-            "v".to_owned()
+        let mut res = match &self.2 {
+            Some(path) => format!("{}:{}", path.display(), self.0),
+            None => format!("{}", self.0),
         };
-    
+
         if self.0.start_ln != self.0.end_ln || self.0.start_cn != self.0.end_cn {
             res.push_str(&format!(" to {}:{}", self.0.end_ln, self.0.end_cn));
         }
 
-        res.push_str(&format!("\n{} |{}{} -- here\n", line_disp_padding, arrow_pad, arrows));
-        
-        for (idx, ln) in (self.0.start_ln..self.0.end_ln + 1).enumerate() {
-            let line = &lines[idx];
-            res.push_str(&format!("{:width$} | {}\n", ln, line, width = line_disp_len));
-        }
+        let label = Label { pos: self.0, message: None, primary: true };
+        res.push('\n');
+        res.push_str(&render_snippet(&self.1, &[label], Style { color: false }));
 
-        res.push_str(&format!("{} |\n", line_disp_padding));
-        
         f.write_str(&res)
     }
 }
@@ -0,0 +1,296 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::diagnostic::{Diagnostic, Label, Severity, Suggestion};
+use crate::source::Source;
+
+const TAB_WIDTH: usize = 4;
+
+// ANSI styling, only applied when rendering for a terminal
+#[derive(Clone, Copy)]
+pub struct Style {
+    pub color: bool,
+}
+
+impl Style {
+    fn paint(&self, text: &str, ansi: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("\x1b[{ansi}m{text}\x1b[0m")
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn severity(&self, severity: Severity, text: &str) -> String {
+        match severity {
+            Severity::Error => self.paint(text, "1;31"),
+            Severity::Warning => self.paint(text, "1;33"),
+        }
+    }
+
+    fn gutter(&self, text: &str) -> String {
+        self.paint(text, "1;34")
+    }
+
+    fn label(&self, primary: bool, severity: Severity, text: &str) -> String {
+        if primary {
+            self.severity(severity, text)
+        } else {
+            self.paint(text, "1;34")
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint(text, "1")
+    }
+
+    fn help(&self, text: &str) -> String {
+        self.paint(text, "1;36")
+    }
+}
+
+// Renders a diagnostic the way rustc does, with a header, the labelled
+// source lines, notes and suggested fixes
+pub fn render(diag: &Diagnostic, style: Style) -> String {
+    let mut res = style.severity(diag.severity, diag.severity.as_str());
+    if let Some(code) = diag.code {
+        res.push_str(&style.severity(diag.severity, &format!("[{code}]")));
+    }
+    res.push_str(&style.bold(&format!(": {}", diag.message)));
+    res.push('\n');
+
+    let src = Source::new(diag.source.as_deref().unwrap_or(""));
+    let width = gutter_width(diag);
+    let pad = " ".repeat(width);
+
+    if let Some(pos) = diag.pos() {
+        let file = diag.path.as_ref()
+            .map(|p| format!("{}:", p.display()))
+            .unwrap_or_default();
+        res.push_str(&format!("{pad}{} {file}{pos}\n", style.gutter("-->")));
+    } else if let Some(path) = &diag.path {
+        res.push_str(&format!("{pad}{} {}\n", style.gutter("-->"), path.display()));
+    }
+
+    if !diag.labels.is_empty() {
+        res.push_str(&snippet(&src, &diag.labels, diag.severity, width, style));
+    }
+
+    if !diag.notes.is_empty() {
+        res.push_str(&format!("{pad} {}\n", style.gutter("|")));
+    }
+
+    for note in &diag.notes {
+        res.push_str(&format!("{pad} {} {}: {note}\n", style.gutter("="), style.bold("note")));
+    }
+
+    for suggestion in &diag.suggestions {
+        res.push_str(&render_suggestion(&src, suggestion, width, style));
+    }
+
+    res
+}
+
+// Renders only the labelled source lines, without a header
+pub fn render_snippet(src: &str, labels: &[Label], style: Style) -> String {
+    let width = labels.iter()
+        .map(|l| digits(l.pos.end_ln))
+        .max()
+        .unwrap_or(1);
+
+    snippet(&Source::new(src), labels, Severity::Error, width, style)
+}
+
+fn snippet(src: &Source, labels: &[Label], severity: Severity, width: usize, style: Style) -> String {
+    let pad = " ".repeat(width);
+    let mut res = format!("{pad} {}\n", style.gutter("|"));
+
+    let mut line_numbers: Vec<usize> = labels.iter()
+        .flat_map(|l| l.pos.start_ln..=l.pos.end_ln)
+        .collect();
+    line_numbers.sort_unstable();
+    line_numbers.dedup();
+
+    let mut prev: Option<usize> = None;
+    for ln in line_numbers {
+        if matches!(prev, Some(p) if ln > p + 1) {
+            res.push_str(&format!("{}\n", style.gutter("...")));
+        }
+        prev = Some(ln);
+
+        let line = src.line(ln);
+        res.push_str(&format!("{} {} {}\n", style.gutter(&format!("{ln:width$}")), style.gutter("|"), expand_tabs(line)));
+
+        for label in labels.iter().filter(|l| (l.pos.start_ln..=l.pos.end_ln).contains(&ln)) {
+            let (start, end) = underline_range(line, label, ln);
+            let marker = if label.primary { "^" } else { "-" };
+            let mut underline = style.label(label.primary, severity, &marker.repeat(end - start));
+
+            if ln == label.pos.end_ln {
+                if let Some(msg) = &label.message {
+                    underline.push(' ');
+                    underline.push_str(&style.label(label.primary, severity, msg));
+                }
+            }
+
+            res.push_str(&format!("{pad} {} {}{underline}\n", style.gutter("|"), " ".repeat(start)));
+        }
+    }
+
+    res
+}
+
+// Display columns covered by the label on the given line
+fn underline_range(line: &str, label: &Label, ln: usize) -> (usize, usize) {
+    let pos = &label.pos;
+    let line_width = width_until(line, usize::MAX);
+
+    let start = if ln == pos.start_ln {
+        width_until(line, pos.start_cn - 1)
+    } else {
+        width_until(line, line.chars().take_while(|c| c.is_whitespace()).count())
+    };
+
+    let end = if ln == pos.end_ln {
+        width_until(line, pos.end_cn)
+    } else {
+        line_width
+    };
+
+    // Spans pointing past the end of the line still get a marker
+    (start, end.max(start + 1))
+}
+
+fn render_suggestion(src: &Source, suggestion: &Suggestion, width: usize, style: Style) -> String {
+    let pad = " ".repeat(width);
+    let pos = &suggestion.pos;
+    let mut res = format!("{}: {}\n", style.help("help"), style.bold(&suggestion.message));
+
+    if pos.start_ln != pos.end_ln && !suggestion.insert {
+        res.push_str(&format!("{pad} {} `{}`\n", style.gutter("="), suggestion.replacement));
+        return res;
+    }
+
+    let line = src.line(pos.start_ln);
+    let start = pos.start_cn - 1;
+    let end = if suggestion.insert { start } else { pos.end_cn };
+
+    let before: String = line.chars().take(start).collect();
+    let after: String = line.chars().skip(end).collect();
+    let patched = format!("{before}{}{after}", suggestion.replacement);
+
+    let offset = width_until(&before, usize::MAX);
+    let replaced = width_until(&suggestion.replacement, usize::MAX);
+    let marker = if suggestion.insert { "+" } else { "~" };
+
+    res.push_str(&format!("{pad} {}\n", style.gutter("|")));
+    res.push_str(&format!("{} {} {}\n", style.gutter(&format!("{:width$}", pos.start_ln)), style.gutter("|"), expand_tabs(&patched)));
+    res.push_str(&format!("{pad} {} {}{}\n", style.gutter("|"), " ".repeat(offset), style.help(&marker.repeat(replaced.max(1)))));
+
+    res
+}
+
+fn gutter_width(diag: &Diagnostic) -> usize {
+    diag.labels.iter()
+        .map(|l| l.pos.end_ln)
+        .chain(diag.suggestions.iter().map(|s| s.pos.end_ln))
+        .map(digits)
+        .max()
+        .unwrap_or(1)
+}
+
+fn digits(n: usize) -> usize {
+    n.to_string().len()
+}
+
+// Display width of the first `chars` characters of a line, taking tabs and
+// wide characters into account
+fn width_until(line: &str, chars: usize) -> usize {
+    line.chars()
+        .take(chars)
+        .map(char_width)
+        .sum()
+}
+
+fn char_width(c: char) -> usize {
+    if c == '\t' {
+        TAB_WIDTH
+    } else {
+        c.width().unwrap_or(0)
+    }
+}
+
+fn expand_tabs(line: &str) -> String {
+    line.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Style};
+    use crate::{diagnostic::Diagnostic, position::Position, transpile};
+
+    fn render_err(src: &str) -> String {
+        let err = transpile(src).unwrap_err();
+        render(&Diagnostic::from_error(&err), Style { color: false })
+    }
+
+    #[test]
+    fn test_render_secondary_label_and_suggestion() {
+        let expect = "\
+error[E0008]: Unclosed block
+ --> 2:10
+  |
+1 | div {
+  |     - unclosed `{` opened here
+2 |   p 'Hi';
+  |          ^ expected `}`
+help: add `}` to close the block
+  |
+2 |   p 'Hi';}
+  |          +
+";
+
+        assert_eq!(render_err("div {\n  p 'Hi';"), expect);
+    }
+
+    #[test]
+    fn test_render_tabs_and_wide_characters() {
+        let src = "\tp '日本' $;";
+        let pos = Position { idx: 12, len: 1, start_ln: 1, start_cn: 9, end_ln: 1, end_cn: 9 };
+        let diag = Diagnostic::error(Some("E0001"), "Invalid character '$'")
+            .with_source(src)
+            .with_label(pos, None);
+
+        let expect = "\
+error[E0001]: Invalid character '$'
+ --> 1:9
+  |
+1 |     p '日本' $;
+  |              ^
+";
+
+        assert_eq!(render(&diag, Style { color: false }), expect);
+    }
+
+    #[test]
+    fn test_render_multiline_span() {
+        let src = "div {\n  p 'one';\n}";
+        let pos = Position { idx: 4, len: 16, start_ln: 1, start_cn: 5, end_ln: 3, end_cn: 1 };
+        let diag = Diagnostic::error(None, "Example")
+            .with_source(src)
+            .with_label(pos, Some("this block"));
+
+        let expect = "\
+error: Example
+ --> 1:5
+  |
+1 | div {
+  |     ^
+2 |   p 'one';
+  |   ^^^^^^^^
+3 | }
+  | ^ this block
+";
+
+        assert_eq!(render(&diag, Style { color: false }), expect);
+    }
+}
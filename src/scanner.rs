@@ -1,3 +1,6 @@
+use std::path::Path;
+
+use crate::diagnostic::Diagnostic;
 use crate::position::ErrorDisplay;
 
 use super::source::Source;
@@ -6,32 +9,94 @@ use super::token::{Token, TokenType};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ScanError {
-    #[error("Invalid character '{0}' at {1}")]
     InvalidCharacter(char, ErrorDisplay),
-
-    #[error("Malformed string at {0}")]
     MalformedString(ErrorDisplay),
-
-    #[error("Malformed HTML at {0}")]
     MalformedHTML(ErrorDisplay),
-
-    #[error("Malformed JS at {0}")]
     MalformedJS(ErrorDisplay),
-
-    #[error("Malformed CSS at {0}")]
     MalformedCSS(ErrorDisplay),
 }
 
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("error[{}]: {} at {}", self.code(), self.message(), self.display()))
+    }
+}
+
+impl ScanError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScanError::InvalidCharacter(..) => "E0001",
+            ScanError::MalformedString(_) => "E0002",
+            ScanError::MalformedHTML(_) => "E0003",
+            ScanError::MalformedJS(_) => "E0004",
+            ScanError::MalformedCSS(_) => "E0005",
+        }
+    }
+
+    // The error message without the source snippet
+    pub fn message(&self) -> String {
+        match self {
+            ScanError::InvalidCharacter(c, _) => format!("Invalid character '{c}'"),
+            ScanError::MalformedString(_) => "Malformed string".to_owned(),
+            ScanError::MalformedHTML(_) => "Malformed HTML".to_owned(),
+            ScanError::MalformedJS(_) => "Malformed JS".to_owned(),
+            ScanError::MalformedCSS(_) => "Malformed CSS".to_owned(),
+        }
+    }
+
+    pub fn pos(&self) -> Position {
+        self.display().pos()
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let pos = self.pos();
+        let diag = Diagnostic::error(Some(self.code()), self.message())
+            .with_path(self.display().path())
+            .with_source(self.display().source());
+
+        match self {
+            ScanError::InvalidCharacter(c, _) => diag
+                .with_label(pos, Some("not allowed here"))
+                .with_note("element and attribute names may only contain letters, digits, `_`, `-`, `:`, `.` and `@`")
+                .with_replacement("wrap it in quotes to use it as text", pos, &format!("'{c}'")),
+            ScanError::MalformedString(_) => diag
+                .with_label(pos.start(self.display().source()), Some("unterminated string"))
+                .with_note("strings must end with the same quote they start with"),
+            ScanError::MalformedHTML(_) => diag
+                .with_label(pos.start(self.display().source()), Some("unclosed `<`"))
+                .with_note("raw HTML ends at the matching `>`"),
+            ScanError::MalformedJS(_) |
+            ScanError::MalformedCSS(_) => diag
+                .with_label(pos.start(self.display().source()), Some("unclosed block"))
+                .with_note("the block ends at the matching `}`"),
+        }
+    }
+
+    fn display(&self) -> &ErrorDisplay {
+        match self {
+            ScanError::InvalidCharacter(_, disp) |
+            ScanError::MalformedString(disp) |
+            ScanError::MalformedHTML(disp) |
+            ScanError::MalformedJS(disp) |
+            ScanError::MalformedCSS(disp) => disp,
+        }
+    }
+}
+
 pub struct Scanner<'a> {
-    src: &'a str,
+    src: Source<'a>,
     pos: Position,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(src: &'a str) -> Self {
+    // The path of the file the source was read from is shown in errors
+    pub fn new(src: &'a str, path: Option<&'a Path>) -> Self {
+        let src = Source::new(src).with_path(path);
+        let pos = Position::new(&src);
+
         Self {
             src,
-            pos: Position::new(),
+            pos,
         }
     }
 
@@ -48,6 +113,11 @@ impl<'a> Scanner<'a> {
             '{' => Ok(self.token(TokenType::LeftBrace)),
             '}' => Ok(self.token(TokenType::RightBrace)),
             ';' => Ok(self.token(TokenType::Semicolon)),
+            ',' => Ok(self.token(TokenType::Comma)),
+
+            '.' if self.if_next('.') && self.if_next('.') => {
+                Ok(self.token(TokenType::Spread))
+            },
 
             '/' if self.if_next('*') => {
                 self.multiline_comment();
@@ -62,8 +132,8 @@ impl<'a> Scanner<'a> {
             '<' => self.html_token(),
 
             '\\' => {
-                self.pos.extend(self.src);
-                self.pos.advance(self.src);
+                self.pos.extend(&self.src);
+                self.pos.advance(&self.src);
                 self.scan()
             }
 
@@ -71,38 +141,37 @@ impl<'a> Scanner<'a> {
             '\r' |
             '\t' |
             ' ' => {
-                self.pos.advance(self.src);
+                self.pos.advance(&self.src);
                 self.scan()
             }
 
-            c if Self::is_letter(c) => self.text_token(),
+            c if Self::is_name_start(c) => self.text_token(),
 
-            _ => Err(ScanError::InvalidCharacter(c, self.pos.for_error(self.src)))
+            c if c.is_ascii_digit() => {
+                self.extend_while(Self::is_name_char);
+                Ok(self.token(TokenType::Number))
+            },
+
+            _ => Err(ScanError::InvalidCharacter(c, self.pos.for_error(&self.src)))
         }
     }
 
     fn multiline_comment(&mut self) {
         while let Some(c) = self.src.peek_next(&self.pos) {
-            self.pos.extend(self.src);
+            self.pos.extend(&self.src);
 
             if c == '*' && matches!(self.src.peek_next(&self.pos), Some('/')) {
-                self.pos.extend(self.src);
-                self.pos.advance(self.src);
+                self.pos.extend(&self.src);
                 break;
             }
         }
+
+        self.pos.advance(&self.src);
     }
 
     fn singleline_comment(&mut self) {
-        while let Some(c) = self.src.peek_next(&self.pos) {
-            self.pos.extend(self.src);
-
-            if c == '\n' {
-                self.pos.extend(self.src);
-                self.pos.advance(self.src);
-                break;
-            }
-        }
+        self.extend_while(|c| c != '\n');
+        self.pos.advance(&self.src);
     }
 
     fn string_token(&mut self) -> Result<Option<Token>, ScanError> {
@@ -113,15 +182,15 @@ impl<'a> Scanner<'a> {
         let next = self.src.peek_next(&self.pos);
         
         if next.is_none() || next.unwrap() != delimeter {
-            return Err(ScanError::MalformedString(self.pos.for_error(self.src)));
+            return Err(ScanError::MalformedString(self.pos.for_error(&self.src)));
         }
 
-        self.pos.extend(self.src);
+        self.pos.extend(&self.src);
         Ok(self.token(TokenType::String))
     }
 
     fn html_token(&mut self) -> Result<Option<Token>, ScanError> {
-        self.pos.extend(self.src);
+        self.pos.extend(&self.src);
 
         let mut depth = 0;
         while let Some(c) = self.src.peek_next(&self.pos) {
@@ -136,19 +205,19 @@ impl<'a> Scanner<'a> {
 
                 depth -= 1;
             }
-            self.pos.extend(self.src);
+            self.pos.extend(&self.src);
         }
 
         if !matches!(self.src.peek_next(&self.pos), Some('>')) {
-            return Err(ScanError::MalformedHTML(self.pos.for_error(self.src)));
+            return Err(ScanError::MalformedHTML(self.pos.for_error(&self.src)));
         }
 
-        self.pos.extend(self.src);
+        self.pos.extend(&self.src);
         Ok(self.token(TokenType::Html))
     }
 
     fn text_token(&mut self) -> Result<Option<Token>, ScanError> {
-        self.extend_while(Self::is_letter_or_digit);
+        self.extend_while(Self::is_name_char);
 
         match (self.src.get_str(&self.pos), &self.src.peek_next(&self.pos)) {
             ("js", Some('{')) => self.parse_js(),
@@ -174,14 +243,14 @@ impl<'a> Scanner<'a> {
 
                 depth -= 1;
             }
-            self.pos.extend(self.src);
+            self.pos.extend(&self.src);
         }
 
         if !matches!(self.src.peek_next(&self.pos), Some('}')) {
-            return Err(ScanError::MalformedJS(self.pos.for_error(self.src)));
+            return Err(ScanError::MalformedJS(self.pos.for_error(&self.src)));
         }
 
-        self.pos.extend(self.src);
+        self.pos.extend(&self.src);
         Ok(self.token(TokenType::Js))
     }
 
@@ -201,14 +270,14 @@ impl<'a> Scanner<'a> {
 
                 depth -= 1;
             }
-            self.pos.extend(self.src);
+            self.pos.extend(&self.src);
         }
 
         if !matches!(self.src.peek_next(&self.pos), Some('}')) {
-            return Err(ScanError::MalformedCSS(self.pos.for_error(self.src)));
+            return Err(ScanError::MalformedCSS(self.pos.for_error(&self.src)));
         }
 
-        self.pos.extend(self.src);
+        self.pos.extend(&self.src);
         Ok(self.token(TokenType::Css))
     }
 
@@ -217,14 +286,14 @@ impl<'a> Scanner<'a> {
             if !func(c) {
                 break;
             }
-            self.pos.extend(self.src);
+            self.pos.extend(&self.src);
         }
     }
 
     fn if_next(&mut self, c: char) -> bool {
         match self.src.peek_next(&self.pos) {
             Some(match_c) if match_c == c => {
-                self.pos.extend(self.src);
+                self.pos.extend(&self.src);
                 true
             },
             _ => false,
@@ -232,7 +301,7 @@ impl<'a> Scanner<'a> {
     }
 
     pub fn pos_error(&self, pos: &Position) -> ErrorDisplay {
-        pos.for_error(self.src)
+        pos.for_error(&self.src)
     } 
 
     pub fn pos(&self) -> Position {
@@ -246,19 +315,28 @@ impl<'a> Scanner<'a> {
             pos: self.pos,
         };
 
-        self.pos.advance(self.src);
+        self.pos.advance(&self.src);
         Some(token)
     }
 
-    fn is_letter(c: char) -> bool {
-        c == '_' || c == '-' || c.is_ascii_alphabetic()
-    }
-
-    fn is_digit(c: char) -> bool {
-        c.is_ascii_digit()
+    // Characters a tag or attribute name can start with. These follow the
+    // NameStartChar production of XML, which HTML and SVG names are a subset
+    // of, plus `-` and the `@` shorthand used by Vue and Alpine.js.
+    fn is_name_start(c: char) -> bool {
+        matches!(c,
+            'a'..='z' | 'A'..='Z' | '_' | ':' | '-' | '@' |
+            '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}' |
+            '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}' |
+            '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}' |
+            '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}'
+        )
     }
 
-    fn is_letter_or_digit(c: char) -> bool {
-        Self::is_letter(c) || Self::is_digit(c)
+    // Characters allowed in a name after the first one, following the NameChar
+    // production of XML. `.` is only allowed here, as in `x-on:click.prevent`.
+    fn is_name_char(c: char) -> bool {
+        Self::is_name_start(c) || matches!(c,
+            '0'..='9' | '.' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}'
+        )
     }
 }
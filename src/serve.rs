@@ -0,0 +1,408 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{check_extension, config::Config, diagnostic::Diagnostic, render, transpile_with};
+
+// Endpoint the injected script listens on for server-sent events
+const EVENTS_PATH: &str = "/__nhtml/events";
+
+// Reloads the page on `reload` events and swaps stylesheets in place on `css` events
+const RELOAD_SCRIPT: &str = r#"<script>
+(() => {
+    const events = new EventSource('/__nhtml/events');
+    events.addEventListener('reload', () => location.reload());
+    events.addEventListener('css', () => {
+        for (const link of document.querySelectorAll('link[rel="stylesheet"]')) {
+            const url = new URL(link.href);
+            url.searchParams.set('nhtml-reload', Date.now());
+            link.href = url.href;
+        }
+    });
+})();
+</script>
+"#;
+
+type Clients = Arc<Mutex<Vec<TcpStream>>>;
+
+// A development server that transpiles nhtml files on request and pushes
+// reload events to connected browsers
+pub struct Server {
+    listener: TcpListener,
+    root: PathBuf,
+    config: Arc<Config>,
+    clients: Clients,
+}
+
+// Handle used to notify connected browsers about changes
+#[derive(Clone)]
+pub struct Reloader {
+    clients: Clients,
+}
+
+impl Reloader {
+    pub fn reload(&self) {
+        self.send("reload");
+    }
+
+    pub fn reload_css(&self) {
+        self.send("css");
+    }
+
+    fn send(&self, event: &str) {
+        let mut clients = self.clients.lock().unwrap();
+
+        // Browsers that went away fail to receive the event and get dropped
+        clients.retain_mut(|client| {
+            client.write_all(format!("event: {event}\ndata: \n\n").as_bytes())
+                .and_then(|_| client.flush())
+                .is_ok()
+        });
+    }
+}
+
+impl Server {
+    // Binds to localhost only, the server is not meant to be reachable from other machines.
+    // Pages are transpiled with the options the config has for them.
+    pub fn bind(root: &Path, port: u16, config: Config) -> Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .context(format!("Failed to bind to port {port}"))?;
+
+        Ok(Self {
+            listener,
+            root: root.to_path_buf(),
+            config: Arc::new(config),
+            clients: Arc::new(Mutex::new(vec![])),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn reloader(&self) -> Reloader {
+        Reloader {
+            clients: self.clients.clone(),
+        }
+    }
+
+    // Accepts connections until the process exits, each on its own thread
+    pub fn run(self) {
+        for stream in self.listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+
+            let root = self.root.clone();
+            let config = self.config.clone();
+            let clients = self.clients.clone();
+
+            std::thread::spawn(move || {
+                if let Err(e) = handle(stream, &root, &config, &clients) {
+                    eprintln!("{e:#}");
+                }
+            });
+        }
+    }
+}
+
+fn handle(mut stream: TcpStream, root: &Path, config: &Config, clients: &Clients) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers, nothing in them changes the response
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"Bad request");
+    };
+
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed");
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+
+    if path == EVENTS_PATH {
+        let mut clients = clients.lock().unwrap();
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n: connected\n\n")?;
+        stream.flush()?;
+        clients.push(stream);
+        return Ok(());
+    }
+
+    let Some(file) = resolve(root, &percent_decode(path)) else {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"Not found");
+    };
+
+    if check_extension(&file, "nhtml") {
+        let input = std::fs::read_to_string(&file)?;
+        let page = match transpile_with(&input, Some(&file), &config.options_for(&file)) {
+            Ok(html) => inject_reload_script(&html),
+            Err(e) => error_page(&e, &file),
+        };
+        return respond(&mut stream, "200 OK", "text/html; charset=utf-8", page.as_bytes());
+    }
+
+    let body = std::fs::read(&file)?;
+    respond(&mut stream, "200 OK", content_type(&file), &body)
+}
+
+// Maps a request path to a file in the root directory. Requests for `.html`
+// files, or paths without an extension, are served from the nhtml source.
+fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => (),
+            _ => return None,
+        }
+    }
+
+    let mut file = root.join(relative);
+
+    if path.ends_with('/') || file.is_dir() {
+        file = file.join("index.html");
+    }
+
+    let source = if check_extension(&file, "html") || file.extension().is_none() {
+        Some(file.with_extension("nhtml"))
+    } else {
+        None
+    };
+
+    match source {
+        Some(source) if source.is_file() => Some(source),
+        _ if file.is_file() => Some(file),
+        _ => None,
+    }
+}
+
+fn inject_reload_script(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(idx) => format!("{}{RELOAD_SCRIPT}{}", &html[..idx], &html[idx..]),
+        None => format!("{html}{RELOAD_SCRIPT}"),
+    }
+}
+
+// Shows the transpile error in the browser, it reloads once the file is fixed
+fn error_page(err: &anyhow::Error, file: &Path) -> String {
+    let diag = Diagnostic::from_error(err).with_path(Some(file));
+    let text = render::render(&diag, render::Style { color: false })
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+
+    format!("<!DOCTYPE html>\n<html>\n<body>\n<pre>{text}</pre>\n{RELOAD_SCRIPT}</body>\n</html>\n")
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        let hex = bytes.get(idx + 1..idx + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Watches the root directory and tells browsers to reload on changes.
+// When only stylesheets changed they are swapped without a full reload.
+pub fn watch(root: &Path, reloader: &Reloader) -> Result<()> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use notify_debouncer_full::new_debouncer;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    let mut debouncer = new_debouncer(Duration::from_millis(250), None, tx)?;
+
+    debouncer.watcher().watch(root, RecursiveMode::Recursive)
+        .context("Failed to start watcher on path")?;
+
+    for res in rx {
+        match res {
+            Ok(events) => {
+                // Serving files causes access events, which must not trigger reloads
+                let changed: Vec<&PathBuf> = events.iter()
+                    .filter(|e| matches!(e.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)))
+                    .flat_map(|e| e.paths.iter())
+                    .collect();
+
+                if changed.is_empty() {
+                    continue;
+                }
+
+                if changed.iter().all(|p| check_extension(p, "css")) {
+                    println!("css changed, updating stylesheets");
+                    reloader.reload_css();
+                } else {
+                    println!("changes detected, reloading");
+                    reloader.reload();
+                }
+            },
+            Err(errors) => errors.iter().for_each(|err| eprintln!("Failed to watch for changes: {err}")),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{SocketAddr, TcpStream},
+        time::Duration,
+    };
+
+    use super::{Reloader, Server};
+    use crate::{config::Config, temp_dir::TempDir};
+
+    fn start(name: &str, files: &[(&str, &str)]) -> (SocketAddr, Reloader, TempDir) {
+        let root = TempDir::new(&format!("serve-{name}"));
+
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        let config = Config::discover(&root).unwrap().unwrap_or_default();
+        let server = Server::bind(&root, 0, config).unwrap();
+        let addr = server.local_addr().unwrap();
+        let reloader = server.reloader();
+        std::thread::spawn(move || server.run());
+
+        (addr, reloader, root)
+    }
+
+    fn connect(addr: SocketAddr, path: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        stream
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut res = String::new();
+        connect(addr, path).read_to_string(&mut res).unwrap();
+        res
+    }
+
+    #[test]
+    fn test_serves_transpiled_pages_and_assets() {
+        let (addr, _, _root) = start("pages", &[
+            ("index.nhtml", "html { body { p 'Home'; } }"),
+            ("blog/post one.nhtml", "p 'Post';"),
+            ("style.css", "p { color: red; }"),
+            ("nhtml.toml", "[variables]\nname = 'Site'\n\n[directories.blog]\nemit = { minify = true }\n"),
+            ("about.nhtml", "p title={name} 'About {{ name }}';"),
+        ]);
+
+        let index = get(addr, "/");
+        assert!(index.starts_with("HTTP/1.1 200 OK"));
+        assert!(index.contains("Home"));
+        assert!(index.contains("EventSource('/__nhtml/events')"));
+        assert!(index.find("EventSource").unwrap() < index.find("</body>").unwrap());
+
+        assert!(get(addr, "/blog/post%20one.html").contains("Post"));
+        assert!(get(addr, "/blog/post%20one").contains("Post"));
+
+        // Pages use the options from nhtml.toml
+        assert!(get(addr, "/blog/post%20one").contains("<p>Post</p><script>"));
+        assert!(get(addr, "/about.html").contains("<p title=\"Site\">\n    About Site\n</p>"));
+
+        let css = get(addr, "/style.css");
+        assert!(css.contains("Content-Type: text/css"));
+        assert!(css.ends_with("p { color: red; }"));
+
+        assert!(get(addr, "/missing.html").starts_with("HTTP/1.1 404"));
+        assert!(get(addr, "/../index.nhtml").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_pushes_reload_events() {
+        let (addr, reloader, _root) = start("events", &[]);
+
+        let mut events = BufReader::new(connect(addr, "/__nhtml/events"));
+        let mut line = String::new();
+        while line != ": connected\n" {
+            line.clear();
+            events.read_line(&mut line).unwrap();
+        }
+
+        reloader.reload_css();
+        reloader.reload();
+
+        let mut received = vec![];
+        while received.len() < 2 {
+            line.clear();
+            events.read_line(&mut line).unwrap();
+            if let Some(event) = line.strip_prefix("event: ") {
+                received.push(event.trim().to_owned());
+            }
+        }
+
+        assert_eq!(received, ["css", "reload"]);
+    }
+}
@@ -1,48 +1,107 @@
+use std::path::Path;
+
 use super::position::Position;
 
-pub trait Source {
+// Source text together with the byte offset every line starts at, so lines
+// can be looked up without rescanning the text. Positions index into the
+// text in bytes, which keeps every lookup constant time. The path of the
+// file the text came from, if any, is kept for error messages.
+pub struct Source<'a> {
+    text: &'a str,
+    path: Option<&'a Path>,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> Source<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        Self {
+            text,
+            path: None,
+            line_starts,
+        }
+    }
+
+    pub fn with_path(mut self, path: Option<&'a Path>) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn path(&self) -> Option<&'a Path> {
+        self.path
+    }
+
     // Get string slice at the specified source
-    fn get_str<'a>(&'a self, pos: &Position) -> &'a str;
+    pub fn get_str(&self, pos: &Position) -> &'a str {
+        &self.text[pos.idx..pos.idx + pos.len]
+    }
 
     // Get character at the end of the position slice
-    fn get_char(&self, pos: &Position) -> Option<char>;
-    
-    // Get character at the start of the position slice
-    fn get_char_start(&self, pos: &Position) -> Option<char>;
+    pub fn get_char(&self, pos: &Position) -> Option<char> {
+        self.get_str(pos).chars().next_back()
+    }
 
     // Gets the character right after the position
-    fn peek_next(&self, pos: &Position) -> Option<char>;
-
-    // Get the lines the position covers
-    fn get_lines(&self, pos: &Position) -> Vec<String>;
-}
-
-impl Source for &str {
-    fn get_str<'a>(&'a self, pos: &Position) -> &'a str {
-        &self[pos.idx..pos.idx + pos.len]
+    pub fn peek_next(&self, pos: &Position) -> Option<char> {
+        self.char_at(pos.idx + pos.len)
     }
 
-    fn get_char(&self, pos: &Position) -> Option<char> {
-        self.chars().nth(pos.idx + pos.len - 1)
+    // Gets the character starting at the byte offset
+    pub fn char_at(&self, idx: usize) -> Option<char> {
+        self.text.get(idx..)?.chars().next()
     }
 
-    fn get_char_start(&self, pos: &Position) -> Option<char> {
-        self.chars().nth(pos.idx)
+    // Gets a line by its 1-based number, without the line ending
+    pub fn line(&self, ln: usize) -> &'a str {
+        let Some(&start) = ln.checked_sub(1).and_then(|idx| self.line_starts.get(idx)) else {
+            return "";
+        };
+        let end = self.line_starts.get(ln).copied().unwrap_or(self.text.len());
+
+        self.text[start..end].trim_end_matches(['\n', '\r'])
     }
+}
 
-    fn peek_next(&self, pos: &Position) -> Option<char> {
-        self.chars().nth(pos.idx + pos.len)
+#[cfg(test)]
+mod tests {
+    use super::Source;
+    use crate::position::Position;
+
+    #[test]
+    fn test_lines() {
+        let src = Source::new("a\r\nbc\n\nd");
+
+        assert_eq!(src.line(1), "a");
+        assert_eq!(src.line(2), "bc");
+        assert_eq!(src.line(3), "");
+        assert_eq!(src.line(4), "d");
+        assert_eq!(src.line(5), "");
     }
 
-    fn get_lines(&self, pos: &Position) -> Vec<String> {
-        if pos.idx >= self.len() {
-            return vec![];
-        }
+    #[test]
+    fn test_multibyte_positions() {
+        let src = Source::new("é\nñb");
+        let mut pos = Position::new(&src);
+
+        assert_eq!(src.get_str(&pos), "é");
+
+        pos.advance(&src);
+        pos.advance(&src);
+        pos.extend(&src);
+
+        assert_eq!(src.get_str(&pos), "ñb");
+        assert_eq!((pos.idx, pos.len), (3, 3));
+        assert_eq!((pos.start_ln, pos.start_cn, pos.end_ln, pos.end_cn), (2, 1, 2, 2));
 
-        self.lines()
-            .skip(pos.start_ln - 1)
-            .take(pos.end_ln - pos.start_ln + 1)
-            .map(String::from)
-            .collect()
+        pos.advance(&src);
+        assert_eq!(src.get_char(&pos), None);
+        assert_eq!((pos.start_ln, pos.start_cn), (2, 3));
     }
 }
@@ -0,0 +1,37 @@
+use std::{ops::Deref, path::{Path, PathBuf}};
+
+// An empty directory for a test, removed again when it goes out of scope so
+// failing tests clean up after themselves too. The path is canonical, like
+// the paths the watcher and the cache work with.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    // The name keeps the directories of tests that run at the same time apart
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("nhtml-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        Self(path.canonicalize().unwrap())
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
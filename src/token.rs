@@ -1,16 +1,20 @@
+use serde::Serialize;
+
 use super::position::Position;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize)]
 pub struct Token {
     pub ty: TokenType,
     pub lexeme: String,
     pub pos: Position,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TokenType {
     Text,
     String,
+    Number,
     Equal,
     LeftBrace,
     RightBrace,
@@ -18,4 +22,6 @@ pub enum TokenType {
     Js,
     Css,
     Semicolon,
+    Comma,
+    Spread,
 }
\ No newline at end of file
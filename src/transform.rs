@@ -0,0 +1,147 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::Result;
+
+use crate::{parser::Element, position::Position};
+
+// A pass over a page, run after variables are replaced and before the page
+// is emitted. Closures taking the elements are transforms too.
+pub trait Transform: Send + Sync {
+    fn transform(&self, elements: &mut Vec<Element>) -> Result<()>;
+}
+
+impl<F> Transform for F
+where
+    F: Fn(&mut Vec<Element>) -> Result<()> + Send + Sync,
+{
+    fn transform(&self, elements: &mut Vec<Element>) -> Result<()> {
+        self(elements)
+    }
+}
+
+// An error a transform can return to point at the part of the page it is
+// about, reported like errors in the source
+#[derive(Debug, thiserror::Error)]
+#[error("{transform}: {message}{}", .related.iter().map(|(message, _)| format!("; {message}")).collect::<String>())]
+pub struct TransformError {
+    pub transform: String,
+    pub message: String,
+    pub pos: Option<Position>,
+    // Other problems found in the same pass, reported along with this one
+    pub related: Vec<(String, Option<Position>)>,
+}
+
+// The transforms pages go through, in the order they were added
+#[derive(Clone, Default)]
+pub struct Transforms(Vec<Arc<dyn Transform>>);
+
+impl Transforms {
+    pub fn push(&mut self, transform: impl Transform + 'static) {
+        self.0.push(Arc::new(transform));
+    }
+
+    // Stops at the first transform that fails
+    pub fn run(&self, elements: &mut Vec<Element>) -> Result<()> {
+        for transform in &self.0 {
+            transform.transform(elements)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Transforms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transforms({})", self.0.len())
+    }
+}
+
+// The same transforms, not transforms that do the same
+impl PartialEq for Transforms {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len() && self.0.iter().zip(&other.0).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
+
+impl Eq for Transforms {}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+
+    use super::TransformError;
+    use crate::{
+        config::Options,
+        parser::{Attrib, Element, Tag, Value},
+        position::{Position, Spanned},
+        transpile_with,
+        walk::{walk_tag_mut, VisitorMut},
+    };
+
+    struct LazyImages;
+
+    impl VisitorMut for LazyImages {
+        fn visit_tag_mut(&mut self, tag: &mut Tag) {
+            if *tag.ty == "img" {
+                let pos = tag.ty.pos;
+                tag.attribs.push(Attrib::Named {
+                    name: Spanned::new("loading".to_owned(), pos),
+                    value: Some(Spanned::new(Value::String("lazy".to_owned()), pos)),
+                });
+            }
+
+            walk_tag_mut(self, tag);
+        }
+    }
+
+    // Prefixes image sources with a CDN, with values that have no position
+    struct Cdn;
+
+    impl VisitorMut for Cdn {
+        fn visit_attrib_mut(&mut self, attrib: &mut Attrib) {
+            if let Attrib::Named { name, value: Some(value) } = attrib {
+                if **name == "src" {
+                    let src = value.as_str().unwrap_or_default();
+                    *value = Spanned::new(Value::String(format!("https://cdn.example.com/{src}")), Position::default());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_transforms() {
+        let opts = Options::default()
+            .with_transform(|elements: &mut Vec<Element>| {
+                LazyImages.visit_elements_mut(elements);
+                Ok(())
+            })
+            .with_transform(|elements: &mut Vec<Element>| {
+                let pos = Position { idx: 0, len: 0, start_ln: 1, start_cn: 1, end_ln: 1, end_cn: 1 };
+                elements.push(Element::Html(Spanned::new("<script src=\"/stats.js\"></script>".to_owned(), pos)));
+                Ok(())
+            });
+
+        let res = transpile_with("div { img src='a.png'; }", None, &opts).unwrap();
+        assert_eq!(res, "<div>\n    <img src=\"a.png\" loading=\"lazy\"></img>\n</div>\n<script src=\"/stats.js\"></script>\n");
+
+        let opts = opts.with_transform(|_: &mut Vec<Element>| bail!("Transform failed"));
+        let err = transpile_with("p;", None, &opts).unwrap_err();
+        assert_eq!(err.to_string(), "Transform failed");
+
+        // Errors pointing at the page are reported like parse errors
+        let opts = Options::default().with_transform(|elements: &mut Vec<Element>| {
+            let pos = elements[1].pos();
+            Err(TransformError { transform: "no-p".to_owned(), message: "`p` is not allowed".to_owned(), pos: Some(pos), related: vec![] }.into())
+        });
+        let err = transpile_with("div;\np 'Hi';", None, &opts).unwrap_err();
+        assert!(err.to_string().starts_with("error[E0012]: `p` is not allowed at 2:1"));
+
+        // Replaced values don't need a position
+        let opts = Options::default().with_transform(|elements: &mut Vec<Element>| {
+            Cdn.visit_elements_mut(elements);
+            Ok(())
+        });
+        let res = transpile_with("div;\n  img alt='' src='a.png';", None, &opts).unwrap();
+        assert_eq!(res, "<div></div>\n<img alt=\"\" src=\"https://cdn.example.com/a.png\"></img>\n");
+    }
+}
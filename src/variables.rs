@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::{parser::{Attrib, Element, Value}, position::{Position, Spanned}, walk};
+
+// A variable from nhtml.toml
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Variable {
+    Text(String),
+    Number(i64),
+    Bool(bool),
+    // A table of attributes, used with `...name`
+    Attributes(BTreeMap<String, Variable>),
+}
+
+impl Variable {
+    // The text `{{ name }}` is replaced with. Tables have none.
+    fn text(&self) -> Option<String> {
+        match self {
+            Variable::Text(text) => Some(text.clone()),
+            Variable::Number(n) => Some(n.to_string()),
+            Variable::Bool(b) => Some(b.to_string()),
+            Variable::Attributes(_) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum VariableError {
+    Unknown(String, Position),
+    // A table used as a value, or a value that was spread
+    WrongType {
+        name: String,
+        pos: Position,
+        expected: &'static str,
+    },
+}
+
+// Replaces `{{ name }}` in text and attribute values with the value of the
+// variable. References to unknown variables are left as they are, so
+// templates meant for Vue or Alpine.js keep working.
+//
+// Attribute values written as `{name}`, names in maps and spreads are
+// replaced by the variable, which has to exist. Variables set to `true` or
+// `false` toggle the attribute.
+pub fn interpolate(elements: &mut [Element], vars: &BTreeMap<String, Variable>) -> Result<(), VariableError> {
+    walk::walk_mut(elements, &mut |element| {
+        match element {
+            Element::Tag(tag) => {
+                for attrib in &mut tag.attribs {
+                    match attrib {
+                        Attrib::Named { value: Some(value), .. } => interpolate_value(value, vars)?,
+                        Attrib::Named { value: None, .. } => {},
+                        Attrib::Spread { name, attribs } => *attribs = spread(name, name.pos, vars)?,
+                    }
+                }
+            },
+            Element::Text(text) if !vars.is_empty() => text.node = interpolate_str(text, vars),
+            Element::Text(_) |
+            Element::Html(_) |
+            Element::Js(_) |
+            Element::Css(_) => {},
+        }
+
+        Ok(())
+    })
+}
+
+fn interpolate_value(value: &mut Spanned<Value>, vars: &BTreeMap<String, Variable>) -> Result<(), VariableError> {
+    match &mut value.node {
+        Value::String(text) if !vars.is_empty() => *text = interpolate_str(text, vars),
+        Value::Variable(name) => value.node = resolve(name, name.pos, vars)?,
+        Value::Map(entries) => {
+            for (_, value) in entries {
+                interpolate_value(value, vars)?;
+            }
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
+
+fn lookup<'v>(name: &str, pos: Position, vars: &'v BTreeMap<String, Variable>) -> Result<&'v Variable, VariableError> {
+    vars.get(name).ok_or_else(|| VariableError::Unknown(name.to_owned(), pos))
+}
+
+fn resolve(name: &str, pos: Position, vars: &BTreeMap<String, Variable>) -> Result<Value, VariableError> {
+    to_value(name, pos, lookup(name, pos, vars)?)
+}
+
+fn to_value(name: &str, pos: Position, var: &Variable) -> Result<Value, VariableError> {
+    match var {
+        Variable::Text(text) => Ok(Value::String(text.clone())),
+        Variable::Number(n) => Ok(Value::Number(n.to_string())),
+        Variable::Bool(b) => Ok(Value::Bool(*b)),
+        Variable::Attributes(_) => Err(VariableError::WrongType { name: name.to_owned(), pos, expected: "a value" }),
+    }
+}
+
+fn spread(name: &str, pos: Position, vars: &BTreeMap<String, Variable>) -> Result<Vec<(String, Option<Value>)>, VariableError> {
+    let Variable::Attributes(attribs) = lookup(name, pos, vars)? else {
+        return Err(VariableError::WrongType { name: name.to_owned(), pos, expected: "a table of attributes" });
+    };
+
+    attribs.iter()
+        .map(|(attr, var)| Ok((attr.clone(), Some(to_value(&format!("{name}.{attr}"), pos, var)?))))
+        .collect()
+}
+
+fn interpolate_str(text: &str, vars: &BTreeMap<String, Variable>) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let name = rest[start + 2..start + len].trim();
+        res.push_str(&rest[..start]);
+
+        match vars.get(name).and_then(Variable::text) {
+            Some(value) => res.push_str(&value),
+            None => res.push_str(&rest[start..start + len + 2]),
+        }
+
+        rest = &rest[start + len + 2..];
+    }
+
+    res.push_str(rest);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{interpolate_str, Variable};
+
+    #[test]
+    fn test_interpolate() {
+        let vars = BTreeMap::from([("title".to_owned(), Variable::Text("Home".to_owned()))]);
+
+        assert_eq!(interpolate_str("{{ title }} | {{title}}", &vars), "Home | Home");
+        assert_eq!(interpolate_str("{{ count }} {{ title", &vars), "{{ count }} {{ title");
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{parser::{Attrib, Element, Tag}, position::Spanned};
+
+// Calls `f` for every element before its children, with the tags it is
+// nested in from the outermost
+pub fn walk<'a>(elements: &'a [Element], f: &mut impl FnMut(&'a Element, &[&'a Tag])) {
+    walk_inner(elements, &mut vec![], f);
+}
+
+fn walk_inner<'a>(elements: &'a [Element], ancestors: &mut Vec<&'a Tag>, f: &mut impl FnMut(&'a Element, &[&'a Tag])) {
+    for element in elements {
+        f(element, ancestors);
+
+        if let Element::Tag(tag) = element {
+            ancestors.push(tag);
+            walk_inner(&tag.body, ancestors, f);
+            ancestors.pop();
+        }
+    }
+}
+
+// Calls `f` for every element before its children, so changes it makes to a
+// tag's body are walked too. Stops at the first error.
+pub fn walk_mut<E>(elements: &mut [Element], f: &mut impl FnMut(&mut Element) -> Result<(), E>) -> Result<(), E> {
+    for element in elements {
+        f(element)?;
+
+        if let Element::Tag(tag) = element {
+            walk_mut(&mut tag.body, f)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Visits the elements of a page. Every method walks the children by
+// default, so implementations only override what they look at and call the
+// matching `walk_` function to keep going.
+pub trait Visitor<'a> {
+    fn visit_elements(&mut self, elements: &'a [Element]) {
+        for element in elements {
+            self.visit_element(element);
+        }
+    }
+
+    fn visit_element(&mut self, element: &'a Element) {
+        walk_element(self, element);
+    }
+
+    fn visit_tag(&mut self, tag: &'a Tag) {
+        walk_tag(self, tag);
+    }
+
+    fn visit_attrib(&mut self, _attrib: &'a Attrib) {}
+
+    fn visit_text(&mut self, _text: &'a Spanned<String>) {}
+
+    // `html`, `js` and `css` blocks
+    fn visit_block(&mut self, _block: &'a Spanned<String>) {}
+}
+
+pub fn walk_element<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, element: &'a Element) {
+    match element {
+        Element::Tag(tag) => visitor.visit_tag(tag),
+        Element::Text(text) => visitor.visit_text(text),
+        Element::Html(block) |
+        Element::Js(block) |
+        Element::Css(block) => visitor.visit_block(block),
+    }
+}
+
+pub fn walk_tag<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, tag: &'a Tag) {
+    for attrib in &tag.attribs {
+        visitor.visit_attrib(attrib);
+    }
+
+    visitor.visit_elements(&tag.body);
+}
+
+// Like `Visitor`, but can change the elements. `visit_elements_mut` gets the
+// whole list, so elements can also be added or removed.
+pub trait VisitorMut {
+    fn visit_elements_mut(&mut self, elements: &mut Vec<Element>) {
+        for element in elements {
+            self.visit_element_mut(element);
+        }
+    }
+
+    fn visit_element_mut(&mut self, element: &mut Element) {
+        walk_element_mut(self, element);
+    }
+
+    fn visit_tag_mut(&mut self, tag: &mut Tag) {
+        walk_tag_mut(self, tag);
+    }
+
+    fn visit_attrib_mut(&mut self, _attrib: &mut Attrib) {}
+
+    fn visit_text_mut(&mut self, _text: &mut Spanned<String>) {}
+
+    fn visit_block_mut(&mut self, _block: &mut Spanned<String>) {}
+}
+
+pub fn walk_element_mut<V: VisitorMut + ?Sized>(visitor: &mut V, element: &mut Element) {
+    match element {
+        Element::Tag(tag) => visitor.visit_tag_mut(tag),
+        Element::Text(text) => visitor.visit_text_mut(text),
+        Element::Html(block) |
+        Element::Js(block) |
+        Element::Css(block) => visitor.visit_block_mut(block),
+    }
+}
+
+pub fn walk_tag_mut<V: VisitorMut + ?Sized>(visitor: &mut V, tag: &mut Tag) {
+    for attrib in &mut tag.attribs {
+        visitor.visit_attrib_mut(attrib);
+    }
+
+    visitor.visit_elements_mut(&mut tag.body);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::walk;
+    use crate::{parser::{Element, Parser}, scanner::Scanner};
+
+    #[test]
+    fn test_walk() {
+        let src = "div { p \"a\"; span; }\nimg;";
+        let mut scanner = Scanner::new(src, None);
+        let elements = Parser::parse(&mut scanner).unwrap();
+
+        let mut seen = vec![];
+        walk(&elements, &mut |element, ancestors| {
+            let name = match element {
+                Element::Tag(tag) => tag.ty.to_string(),
+                _ => "text".to_owned(),
+            };
+            let pos = element.pos();
+            seen.push((name, ancestors.len(), pos.start_ln, pos.start_cn));
+        });
+
+        assert_eq!(seen, vec![
+            ("div".to_owned(), 0, 1, 1),
+            ("p".to_owned(), 1, 1, 7),
+            ("text".to_owned(), 2, 1, 9),
+            ("span".to_owned(), 1, 1, 14),
+            ("img".to_owned(), 0, 2, 1),
+        ]);
+    }
+}
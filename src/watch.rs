@@ -0,0 +1,437 @@
+use std::{path::{Path, PathBuf}, time::Duration};
+
+use anyhow::{Context, Result};
+use notify::{event::{CreateKind, ModifyKind, RenameMode}, Event, EventKind, RecursiveMode, Watcher};
+
+use crate::{assets::copy_asset, build_page, cache::BuildCache, check_extension, config::Config, report_diagnostics, report_error, transpile_dir, transpile_from_to, ErrorFormat, Summary};
+
+// The input and output being watched, with the input canonicalized so it
+// matches the paths reported by the watcher
+struct Paths {
+    input: PathBuf,
+    output: PathBuf,
+    single_file: bool,
+}
+
+impl Paths {
+    fn new(input: &Path, output: &Path) -> Result<Self> {
+        let input = input.canonicalize()
+            .context(format!("Failed to find '{}'", input.display()))?;
+        let single_file = !input.is_dir();
+
+        // A single file written into a directory keeps its name
+        let output = match input.file_name() {
+            Some(name) if single_file && output.is_dir() => output.join(name).with_extension("html"),
+            _ => output.to_path_buf(),
+        };
+
+        Ok(Self {
+            input,
+            output,
+            single_file,
+        })
+    }
+
+    // Where the output of a source file or directory goes, if it is inside the input
+    fn output_for(&self, path: &Path) -> Option<PathBuf> {
+        if self.single_file {
+            return (path == self.input).then(|| self.output.clone());
+        }
+
+        // Files written to an output inside the input are not sources
+        if self.output.canonicalize().is_ok_and(|out| path.starts_with(out)) {
+            return None;
+        }
+
+        let relative = path.strip_prefix(&self.input).ok()?;
+        if relative.as_os_str().is_empty() {
+            return None;
+        }
+
+        let out = self.output.join(relative);
+        if check_extension(path, "nhtml") {
+            Some(out.with_extension("html"))
+        } else {
+            Some(out)
+        }
+    }
+
+    fn display<'a>(&self, path: &'a Path) -> std::path::Display<'a> {
+        path.strip_prefix(self.input.parent().unwrap_or(&self.input))
+            .unwrap_or(path)
+            .display()
+    }
+}
+
+// Everything needed to rebuild parts of the output as events come in
+struct Project {
+    paths: Paths,
+    config: Config,
+    cache: BuildCache,
+    format: ErrorFormat,
+}
+
+pub fn watch(input: &Path, output: &Path, config: Config, format: ErrorFormat) -> Result<()> {
+    use notify_debouncer_full::new_debouncer;
+
+    let mut project = Project::new(input, output, config, format)?;
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    let mut debouncer = new_debouncer(Duration::from_millis(250), None, tx)?;
+
+    debouncer.watcher().watch(&project.paths.input, RecursiveMode::Recursive)
+        .context("Failed to start watcher on path")?;
+
+    let mut watched = vec![];
+    watch_config(debouncer.watcher(), &project, &mut watched);
+
+    for res in rx {
+        match res {
+            Ok(events) => {
+                events.iter().for_each(|event| {
+                    if let Err(e) = project.event(event) {
+                        report_error(&e, format);
+                    }
+                });
+
+                if let Err(e) = project.cache.save() {
+                    report_error(&e, format);
+                }
+
+                // A reloaded configuration can have new plugins
+                watch_config(debouncer.watcher(), &project, &mut watched);
+            },
+            Err(errors) => errors.into_iter().for_each(|err| {
+                report_error(&anyhow::Error::new(err).context("Failed to watch for changes"), format);
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+// Watches the directories holding nhtml.toml and the plugins, which are
+// usually outside the input, so the configuration is loaded again when they
+// change
+fn watch_config(watcher: &mut impl Watcher, project: &Project, watched: &mut Vec<PathBuf>) {
+    for file in project.config_files() {
+        let Some(dir) = file.parent() else {
+            continue;
+        };
+
+        if dir.starts_with(&project.paths.input) || watched.iter().any(|w| w == dir) {
+            continue;
+        }
+
+        match watcher.watch(dir, RecursiveMode::NonRecursive) {
+            Ok(()) => watched.push(dir.to_path_buf()),
+            Err(e) => report_error(&anyhow::Error::new(e).context(format!("Failed to watch '{}'", dir.display())), project.format),
+        }
+    }
+}
+
+impl Project {
+    fn new(input: &Path, output: &Path, config: Config, format: ErrorFormat) -> Result<Self> {
+        let paths = Paths::new(input, output)?;
+        let cache = BuildCache::for_output(&paths.input, &paths.output);
+
+        Ok(Self {
+            paths,
+            config,
+            cache,
+            format,
+        })
+    }
+
+    fn event(&mut self, event: &Event) -> Result<()> {
+        let config_files = self.config_files();
+        let changes = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_));
+        if changes && event.paths.iter().any(|path| config_files.contains(path)) {
+            self.reload()?;
+        }
+
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                self.moved(&event.paths[0], &event.paths[1])
+            },
+            EventKind::Create(CreateKind::Folder) => {
+                event.paths.iter().try_for_each(|path| self.created_dir(path))
+            },
+            EventKind::Create(_) |
+            EventKind::Modify(_) |
+            EventKind::Remove(_) => {
+                event.paths.iter().try_for_each(|path| self.changed(path))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    // Rebuilds a changed file and every page built from it, or copies it if
+    // it is an asset. Removes its output if it no longer exists.
+    fn changed(&self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return self.removed(path);
+        }
+
+        if path.is_dir() {
+            return Ok(());
+        }
+
+        if self.config.is_asset(path) && !self.paths.single_file {
+            if let Some(out) = self.paths.output_for(path) {
+                if copy_asset(path, &out, self.config.hard_link)? {
+                    println!("copied {} -> {}", self.paths.display(path), out.display());
+                }
+            }
+        }
+
+        let mut pages = self.cache.dependents(path);
+        if check_extension(path, "nhtml") && !self.config.is_ignored(path) && !pages.iter().any(|page| page == path) {
+            pages.push(path.to_path_buf());
+        }
+
+        self.rebuild(&pages);
+        Ok(())
+    }
+
+    // Builds the pages, reporting every one that fails along with the
+    // warnings of the others
+    fn rebuild(&self, pages: &[PathBuf]) {
+        let mut summary = Summary::default();
+
+        for page in pages {
+            let Some(out) = self.paths.output_for(page) else {
+                continue;
+            };
+
+            let res = build_page(page, &out, &self.config, &self.cache);
+            if matches!(res, Ok(Some(_))) {
+                println!("built {} -> {}", self.paths.display(page), out.display());
+            }
+            summary.add(res);
+        }
+
+        report_diagnostics(&summary.diagnostics(), self.format);
+    }
+
+    // Builds every file in a directory that was created or moved into the
+    // input, reporting each page that fails
+    fn created_dir(&self, path: &Path) -> Result<()> {
+        let paths = &self.paths;
+        if paths.single_file || !path.is_dir() || paths.output_for(path).is_none() || self.config.is_ignored(path) {
+            return Ok(());
+        }
+
+        let summary = transpile_dir(&paths.input, path, &paths.output, &self.config, &self.cache)?;
+        report_diagnostics(&summary.diagnostics(), self.format);
+        println!("built {} ({} pages)", paths.display(path), summary.built);
+        Ok(())
+    }
+
+    // nhtml.toml and the plugins, canonical so they match the paths of events
+    fn config_files(&self) -> Vec<PathBuf> {
+        self.config.file.iter()
+            .chain(&self.config.plugins)
+            .map(|file| file.canonicalize().unwrap_or_else(|_| file.clone()))
+            .collect()
+    }
+
+    // Loads nhtml.toml and its plugins again and builds every page with the
+    // new options. The old configuration is kept if the new one fails to load.
+    fn reload(&mut self) -> Result<()> {
+        let Some(file) = self.config.file.clone() else {
+            return Ok(());
+        };
+
+        self.config = Config::load(&file)?;
+        println!("reloaded {}", file.display());
+
+        let paths = &self.paths;
+        let summary = transpile_from_to(&paths.input, &paths.output, &self.config, &self.cache)?;
+        report_diagnostics(&summary.diagnostics(), self.format);
+        println!("built {} ({} pages)", paths.display(&paths.input), summary.built);
+        Ok(())
+    }
+
+    // Removes the output of a deleted source and rebuilds the pages that
+    // were built from it
+    fn removed(&self, path: &Path) -> Result<()> {
+        self.cache.forget(path);
+
+        let pages = self.cache.dependents(path);
+        self.rebuild(&pages);
+
+        let Some(out) = self.paths.output_for(path) else {
+            return Ok(());
+        };
+
+        if (check_extension(path, "nhtml") || self.config.is_asset(path)) && out.is_file() {
+            std::fs::remove_file(&out)
+                .context(format!("Failed to remove '{}'", out.display()))?;
+            println!("removed {}", out.display());
+        } else if !self.paths.single_file && out.is_dir() {
+            std::fs::remove_dir_all(&out)
+                .context(format!("Failed to remove '{}'", out.display()))?;
+            println!("removed {}", out.display());
+        }
+
+        Ok(())
+    }
+
+    // Moves the output along with a renamed source. Renames into or out of
+    // the input, or to a different extension, are handled as a create and a
+    // remove.
+    fn moved(&self, from: &Path, to: &Path) -> Result<()> {
+        let from_out = self.paths.output_for(from);
+        let to_out = self.paths.output_for(to);
+
+        let same_kind = (check_extension(from, "nhtml") && check_extension(to, "nhtml")) ||
+            (self.config.is_asset(from) && self.config.is_asset(to)) ||
+            (from_out.as_ref().is_some_and(|out| out.is_dir()) && to.is_dir());
+
+        if let (Some(from_out), Some(to_out)) = (&from_out, &to_out) {
+            if same_kind && from_out.exists() && !self.config.is_ignored(to) {
+                if let Some(parent) = to_out.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(from_out, to_out)
+                    .context(format!("Failed to move '{}'", from_out.display()))?;
+                println!("moved {} -> {}", from_out.display(), to_out.display());
+
+                // Pages are built again, `[directories]` can give them other
+                // options in their new place
+                self.cache.forget(from);
+                let mut pages = self.cache.dependents(from);
+                if to.is_dir() {
+                    self.created_dir(to)?;
+                } else if check_extension(to, "nhtml") {
+                    pages.push(to.to_path_buf());
+                }
+                self.rebuild(&pages);
+                return Ok(());
+            }
+        }
+
+        self.removed(from)?;
+
+        if to.is_dir() {
+            self.created_dir(to)
+        } else {
+            self.changed(to)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use notify::{event::{CreateKind, ModifyKind, RemoveKind, RenameMode, DataChange}, Event, EventKind};
+
+    use super::Project;
+    use crate::{config::Config, temp_dir::TempDir, ErrorFormat};
+
+    fn setup(name: &str) -> TempDir {
+        let root = TempDir::new(&format!("watch-{name}"));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        root
+    }
+
+    fn event(kind: EventKind, paths: &[&Path]) -> Event {
+        paths.iter().fold(Event::new(kind), |e, p| e.add_path(p.to_path_buf()))
+    }
+
+    #[test]
+    fn test_create_modify_rename_remove() {
+        let root = setup("files");
+        std::fs::write(root.join("nhtml.toml"), "[directories.\"src/blog\"]\nemit = { minify = true }\n").unwrap();
+        let config = Config::load(&root.join("nhtml.toml")).unwrap();
+        let mut project = Project::new(&root.join("src"), &root.join("out"), config, ErrorFormat::Human).unwrap();
+        let src = project.paths.input.clone();
+        let out = root.join("out");
+
+        std::fs::create_dir_all(src.join("blog")).unwrap();
+        std::fs::write(src.join("blog/post.nhtml"), "p 'One';").unwrap();
+        project.event(&event(EventKind::Create(CreateKind::File), &[&src.join("blog/post.nhtml")])).unwrap();
+        assert_eq!(std::fs::read_to_string(out.join("blog/post.html")).unwrap(), "<p>One</p>");
+
+        std::fs::write(src.join("blog/post.nhtml"), "p 'Two';").unwrap();
+        project.event(&event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &[&src.join("blog/post.nhtml")])).unwrap();
+        assert!(std::fs::read_to_string(out.join("blog/post.html")).unwrap().contains("Two"));
+
+        std::fs::rename(src.join("blog/post.nhtml"), src.join("post.nhtml")).unwrap();
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        project.event(&event(rename, &[&src.join("blog/post.nhtml"), &src.join("post.nhtml")])).unwrap();
+        assert!(!out.join("blog/post.html").exists());
+        assert_eq!(std::fs::read_to_string(out.join("post.html")).unwrap(), "<p>\n    Two\n</p>\n");
+
+        std::fs::rename(src.join("post.nhtml"), src.join("post.txt")).unwrap();
+        project.event(&event(rename, &[&src.join("post.nhtml"), &src.join("post.txt")])).unwrap();
+        assert!(!out.join("post.html").exists());
+        assert!(out.join("post.txt").exists());
+
+        std::fs::rename(src.join("post.txt"), src.join("blog/notes.txt")).unwrap();
+        project.event(&event(rename, &[&src.join("post.txt"), &src.join("blog/notes.txt")])).unwrap();
+        assert!(!out.join("post.txt").exists());
+        assert_eq!(std::fs::read_to_string(out.join("blog/notes.txt")).unwrap(), "p 'Two';");
+
+        std::fs::remove_dir_all(src.join("blog")).unwrap();
+        project.event(&event(EventKind::Remove(RemoveKind::Folder), &[&src.join("blog")])).unwrap();
+        assert!(!out.join("blog").exists());
+    }
+
+    #[test]
+    fn test_config_reload() {
+        let root = setup("config");
+        let file = root.join("nhtml.toml");
+        std::fs::write(&file, "").unwrap();
+        std::fs::write(root.join("src/index.nhtml"), "p 'One';").unwrap();
+        let config = Config::load(&file).unwrap();
+        let mut project = Project::new(&root.join("src"), &root.join("out"), config, ErrorFormat::Human).unwrap();
+        let out = root.join("out/index.html");
+
+        project.event(&event(EventKind::Create(CreateKind::File), &[&root.join("src/index.nhtml")])).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "<p>\n    One\n</p>\n");
+
+        // Pages are built again with the new options
+        std::fs::write(&file, "[emit]\nminify = true\n").unwrap();
+        project.event(&event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &[&file])).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "<p>One</p>");
+
+        // A broken file keeps the options that were loaded last
+        std::fs::write(&file, "[emit\n").unwrap();
+        assert!(project.event(&event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &[&file])).is_err());
+        std::fs::write(root.join("src/index.nhtml"), "p 'Two';").unwrap();
+        project.event(&event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &[&root.join("src/index.nhtml")])).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "<p>Two</p>");
+    }
+
+    #[test]
+    fn test_rebuild_reports_every_page() {
+        let root = setup("rebuild");
+        let project = Project::new(&root.join("src"), &root.join("out"), Config::default(), ErrorFormat::Human).unwrap();
+        let src = project.paths.input.clone();
+
+        std::fs::write(src.join("broken.nhtml"), "p 'Unclosed").unwrap();
+        std::fs::write(src.join("index.nhtml"), "p 'Index';").unwrap();
+        project.rebuild(&[src.join("broken.nhtml"), src.join("index.nhtml")]);
+
+        // A failing page doesn't stop the ones after it
+        assert!(!root.join("out/broken.html").exists());
+        assert!(root.join("out/index.html").exists());
+    }
+
+    #[test]
+    fn test_directory_moved_in() {
+        let root = setup("dirs");
+        let config = Config::default();
+        let mut project = Project::new(&root.join("src"), &root.join("out"), config, ErrorFormat::Human).unwrap();
+        let src = project.paths.input.clone();
+
+        std::fs::create_dir_all(src.join("docs/guide")).unwrap();
+        std::fs::write(src.join("docs/guide/intro.nhtml"), "p 'Intro';").unwrap();
+        project.event(&event(EventKind::Create(CreateKind::Folder), &[&src.join("docs")])).unwrap();
+
+        assert!(root.join("out/docs/guide/intro.html").exists());
+    }
+}
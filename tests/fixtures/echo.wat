@@ -0,0 +1,25 @@
+;; Answers with the page it was given, unchanged. Built into echo.wasm with
+;; `wat2wasm echo.wat`.
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+
+  ;; Bump allocator, growing the memory when the input doesn't fit
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local $end i32)
+    (local.set $ptr (global.get $next))
+    (local.set $end (i32.add (local.get $ptr) (local.get $len)))
+    (if (i32.gt_u (local.get $end) (i32.mul (memory.size) (i32.const 65536)))
+      (then
+        (drop (memory.grow
+          (i32.sub
+            (i32.add (i32.shr_u (local.get $end) (i32.const 16)) (i32.const 1))
+            (memory.size))))))
+    (global.set $next (local.get $end))
+    (local.get $ptr))
+
+  (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len)))))
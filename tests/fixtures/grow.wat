@@ -0,0 +1,13 @@
+;; Grows its memory until that fails, to check that plugins are stopped when
+;; they use too much of it.
+;; Built into grow.wasm with `wat2wasm grow.wat`.
+(module
+  (memory (export "memory") 1)
+
+  (func (export "alloc") (param $len i32) (result i32)
+    (i32.const 1024))
+
+  (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+    (loop $grow
+      (br_if $grow (i32.ne (memory.grow (i32.const 16)) (i32.const -1))))
+    (i64.const 0)))
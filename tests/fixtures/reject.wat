@@ -0,0 +1,12 @@
+;; Rejects every page with three diagnostics, one without a position, one
+;; pointing at the first three bytes and one at the two bytes after `div; `.
+;; Built into reject.wasm with `wat2wasm reject.wat`.
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 16) "{\"diagnostics\":[{\"message\":\"Pages need a title\"},{\"message\":\"Not allowed here\",\"pos\":{\"idx\":0,\"len\":3,\"start_ln\":1,\"start_cn\":1,\"end_ln\":1,\"end_cn\":3}},{\"message\":\"Not allowed either\",\"pos\":{\"idx\":5,\"len\":2,\"start_ln\":1,\"start_cn\":6,\"end_ln\":1,\"end_cn\":7}}]}")
+
+  (func (export "alloc") (param $len i32) (result i32)
+    (i32.const 1024))
+
+  (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 258))))
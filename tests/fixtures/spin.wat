@@ -0,0 +1,12 @@
+;; Never returns from `transform`, to check that plugins are stopped.
+;; Built into spin.wasm with `wat2wasm spin.wat`.
+(module
+  (memory (export "memory") 1)
+
+  (func (export "alloc") (param $len i32) (result i32)
+    (i32.const 1024))
+
+  (func (export "transform") (param $ptr i32) (param $len i32) (result i64)
+    (loop $spin
+      (br $spin))
+    (i64.const 0)))